@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::Serialize;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 
@@ -10,22 +12,57 @@ pub struct Userdata {
     id: u32,
     username: String,
     email: Option<String>,
+    #[serde(flatten)]
+    summary: ProfileSummary,
+}
+
+/// Aggregate stats shown on a user's profile.
+#[derive(Debug, Serialize)]
+pub struct ProfileSummary {
+    /// Number of scores set with each grade, keyed by `Grade::key`.
+    grades: BTreeMap<String, i64>,
+}
+
+pub async fn get_profile_summary(pool: &PgPool, id: u32) -> Result<ProfileSummary, sqlx::Error> {
+    // Scores submitted before grades were recorded have no grade and aren't counted.
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT grade, COUNT(*) FROM scores WHERE user_id = $1 AND grade IS NOT NULL GROUP BY grade;",
+    )
+    .bind(i32::try_from(id).unwrap())
+    .fetch_all(pool)
+    .await?;
+    Ok(ProfileSummary {
+        grades: rows.into_iter().collect(),
+    })
+}
+
+#[derive(Debug)]
+pub enum GetUserError {
+    NotFound,
+    /// The query failed, e.g. because Azusa hasn't migrated the database yet.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for GetUserError {
+    fn from(e: sqlx::Error) -> Self {
+        GetUserError::Database(e)
+    }
 }
 
-pub async fn get_user_by_id(pool: &PgPool, id: u32) -> Result<Userdata, ()> {
+pub async fn get_user_by_id(pool: &PgPool, id: u32) -> Result<Userdata, GetUserError> {
     let (username, email): (String, String) =
         match sqlx::query_as("SELECT username, email FROM users WHERE user_id = $1;")
             .bind(i32::try_from(id).unwrap())
             .fetch_optional(pool)
-            .await
-            .unwrap()
+            .await?
         {
             Some(data) => data,
-            None => return Err(()),
+            None => return Err(GetUserError::NotFound),
         };
     Ok(Userdata {
         id,
         username,
         email: Some(email),
+        summary: get_profile_summary(pool, id).await?,
     })
 }
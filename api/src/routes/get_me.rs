@@ -3,19 +3,21 @@ use serde::Serialize;
 use sqlx::PgPool;
 
 use crate::{
-    db::{self, Userdata},
+    db::{self, GetUserError, Userdata},
     UserIdFromToken,
 };
 
 #[derive(Debug)]
 pub enum GetMeError {
     NotFound,
+    Database,
 }
 
 impl std::fmt::Display for GetMeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             GetMeError::NotFound => writeln!(f, "{}", serde_json::json!({ "error": "not-found" })),
+            GetMeError::Database => writeln!(f, "{}", serde_json::json!({ "error": "database" })),
         }
     }
 }
@@ -34,7 +36,8 @@ pub async fn get_me(
 ) -> Result<web::Json<GetMeResponse>, GetMeError> {
     let userdata = match db::get_user_by_id(pool.get_ref(), user_id).await {
         Ok(d) => d,
-        Err(_) => return Err(GetMeError::NotFound),
+        Err(GetUserError::NotFound) => return Err(GetMeError::NotFound),
+        Err(GetUserError::Database(_)) => return Err(GetMeError::Database),
     };
     Ok(web::Json(GetMeResponse { userdata }))
 }
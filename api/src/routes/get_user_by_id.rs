@@ -2,11 +2,12 @@ use actix_web::web;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
-use crate::db::{self, Userdata};
+use crate::db::{self, GetUserError, Userdata};
 
 #[derive(Debug)]
 pub enum GetUserByIdError {
     NotFound,
+    Database,
 }
 
 impl std::fmt::Display for GetUserByIdError {
@@ -15,6 +16,9 @@ impl std::fmt::Display for GetUserByIdError {
             GetUserByIdError::NotFound => {
                 writeln!(f, "{}", serde_json::json!({ "error": "not-found" }))
             }
+            GetUserByIdError::Database => {
+                writeln!(f, "{}", serde_json::json!({ "error": "database" }))
+            }
         }
     }
 }
@@ -39,7 +43,8 @@ pub async fn get_user_by_id(
     let user_id = path.into_inner().user_id;
     let userdata = match db::get_user_by_id(pool.get_ref(), user_id).await {
         Ok(d) => d,
-        Err(_) => return Err(GetUserByIdError::NotFound),
+        Err(GetUserError::NotFound) => return Err(GetUserByIdError::NotFound),
+        Err(GetUserError::Database(_)) => return Err(GetUserByIdError::Database),
     };
     Ok(web::Json(GetUserByIdResponse { userdata }))
 }
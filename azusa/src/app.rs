@@ -73,11 +73,21 @@ CREATE TABLE IF NOT EXISTS sessions (
             "
 CREATE TABLE IF NOT EXISTS scores (
     user_id INT NOT NULL,
-    diff_id INTEGER NOT NULL,
-    hit_count INTEGER NOT NULL,
+    diff_id INTEGER,
+    diff_hash TEXT,
+    fruit_count INTEGER NOT NULL,
+    droplet_count INTEGER NOT NULL DEFAULT 0,
+    tiny_droplet_count INTEGER NOT NULL DEFAULT 0,
+    tiny_droplet_miss_count INTEGER NOT NULL DEFAULT 0,
+    banana_count INTEGER NOT NULL DEFAULT 0,
+    banana_miss_count INTEGER NOT NULL DEFAULT 0,
     miss_count INTEGER NOT NULL,
     score INTEGER NOT NULL,
     top_combo INTEGER NOT NULL,
+    mods TEXT NOT NULL DEFAULT '[]',
+    model TEXT NOT NULL DEFAULT 'combo-weighted',
+    grade TEXT,
+    pp REAL NOT NULL DEFAULT 0,
 
     CONSTRAINT fk_user
       FOREIGN KEY(user_id)
@@ -88,6 +98,49 @@ CREATE TABLE IF NOT EXISTS scores (
         .execute(&pool)
         .await
         .unwrap();
+        // Databases created before scores recorded mods, every judgement kind, the scoring model, the grade and pp.
+        sqlx::query(
+            "
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM information_schema.columns WHERE table_name = 'scores' AND column_name = 'hit_count') THEN
+        ALTER TABLE scores RENAME COLUMN hit_count TO fruit_count;
+    END IF;
+END $$;
+        ",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "
+ALTER TABLE scores
+    ADD COLUMN IF NOT EXISTS droplet_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS tiny_droplet_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS tiny_droplet_miss_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS banana_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS banana_miss_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS mods TEXT NOT NULL DEFAULT '[]',
+    ADD COLUMN IF NOT EXISTS model TEXT NOT NULL DEFAULT 'combo-weighted',
+    ADD COLUMN IF NOT EXISTS grade TEXT,
+    ADD COLUMN IF NOT EXISTS pp REAL NOT NULL DEFAULT 0;
+        ",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        // Scores used to be keyed by difficulty ids that only meant something to the client that submitted them.
+        // Those keep their id and count towards the ranking, but aren't on any leaderboard.
+        sqlx::query(
+            "
+ALTER TABLE scores
+    ADD COLUMN IF NOT EXISTS diff_hash TEXT,
+    ALTER COLUMN diff_id DROP NOT NULL;
+        ",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
 
         let app: &'static App = Box::leak(Box::new(App {
             tx,
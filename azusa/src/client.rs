@@ -1,14 +1,13 @@
 use crate::app::{App, Target};
 use client::{
-    azusa::{ClientPacket, ServerPacket},
+    azusa::{ClientPacket, RankingEntry, ServerPacket},
     chat::{ChatMessage, ChatMessagePacket},
-    rulesets::{
-        catch::{CatchJudgement, CatchScore},
-        JudgementResult,
-    },
+    mods::Mod,
+    rulesets::catch::{CatchScore, JUDGEMENT_COLUMNS},
+    score::{self, Grade, ScoringModel},
 };
 use sqlx::Row;
-use std::{collections::BTreeMap, time::Instant};
+use std::time::Instant;
 
 pub struct Client {
     last_ping: Instant,
@@ -67,58 +66,141 @@ impl Client {
             ClientPacket::Login(_) => panic!("Can't login after already being logged in!"),
             ClientPacket::Submit(score) => {
                 println!("Submitting score for {}", self.username);
-                sqlx::query("INSERT INTO scores(user_id, diff_id, hit_count, miss_count, score, top_combo) VALUES ($1, $2, $3, $4, $5, $6)")
-                .bind(i32::try_from(self.user_id).unwrap())
-                .bind(i32::try_from(score.diff_id).unwrap())
-                .bind(i32::try_from(score.judgements[&JudgementResult::Hit(CatchJudgement::Perfect)]).unwrap())
-                .bind(i32::try_from(score.judgements[&JudgementResult::Miss]).unwrap())
-                .bind(i32::try_from(score.score).unwrap())
-                .bind(i32::try_from(score.top_combo).unwrap()).execute(&self.app.pool).await.unwrap();
+                let counts = score::judgement_counts(&score.judgements);
+                let query = format!(
+                    "INSERT INTO scores(user_id, diff_hash, {}, score, top_combo, mods, model, grade, pp) VALUES ({})",
+                    JUDGEMENT_COLUMNS.join(", "),
+                    (1..=counts.len() + 8)
+                        .map(|idx| format!("${}", idx))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                let mut query = sqlx::query(&query)
+                    .bind(i32::try_from(self.user_id).unwrap())
+                    .bind(score.diff_hash.as_str());
+                for count in counts {
+                    query = query.bind(i32::try_from(count).unwrap());
+                }
+                query
+                    .bind(i32::try_from(score.score).unwrap())
+                    .bind(i32::try_from(score.top_combo).unwrap())
+                    .bind(serde_json::to_string(&score.mods).unwrap())
+                    .bind(score.model.key())
+                    .bind(score.grade.key())
+                    .bind(score.pp)
+                    .execute(&self.app.pool)
+                    .await
+                    .unwrap();
+
+                let ranking = self.ranking().await;
+                if let Some((rank, entry)) = ranking
+                    .iter()
+                    .enumerate()
+                    .find(|(_, entry)| entry.username == self.username)
+                {
+                    self.app.send(
+                        Target::User(self.username.clone()),
+                        ServerPacket::Chat(ChatMessagePacket(ChatMessage {
+                            username: "Azusa".to_owned(),
+                            content: format!(
+                                "You are now rank #{} with {:.0}pp.",
+                                rank + 1,
+                                entry.pp
+                            ),
+                        })),
+                    );
+                }
             }
-            ClientPacket::RequestLeaderboard(diff_id) => {
-                let scores = sqlx::query(
+            ClientPacket::RequestLeaderboard(diff_hash) => {
+                let query = format!(
                     "
-                    SELECT username, hit_count, miss_count, score, top_combo
+                    SELECT username, {}, score, top_combo, mods, model, grade, pp
                         FROM scores
                         INNER JOIN users ON (users.user_id = scores.user_id)
-                        WHERE diff_id = $1
+                        WHERE diff_hash = $1
                         ORDER BY score DESC
                         ",
-                )
-                .bind(i32::try_from(diff_id).unwrap())
-                .map(|row: sqlx::postgres::PgRow| {
-                    let username: String = row.try_get(0).unwrap();
-                    let hit_count: i32 = row.try_get(1).unwrap();
-                    let miss_count: i32 = row.try_get(2).unwrap();
-                    let score: i32 = row.try_get(3).unwrap();
-                    let top_combo: i32 = row.try_get(4).unwrap();
-                    CatchScore {
-                        username: Some(username),
-                        diff_id,
-                        score: score.try_into().unwrap(),
-                        top_combo: top_combo.try_into().unwrap(),
-                        passed: true,
-                        judgements: {
-                            let mut judgements = BTreeMap::new();
-                            judgements.insert(
-                                JudgementResult::Hit(CatchJudgement::Perfect),
-                                hit_count.try_into().unwrap(),
-                            );
-                            judgements
-                                .insert(JudgementResult::Miss, miss_count.try_into().unwrap());
-                            judgements
-                        },
-                    }
-                })
-                .fetch_all(&self.app.pool)
-                .await
-                .unwrap();
+                    JUDGEMENT_COLUMNS.join(", ")
+                );
+                let scores = sqlx::query(&query)
+                    .bind(diff_hash.as_str())
+                    .map(|row: sqlx::postgres::PgRow| {
+                        let username: String = row.try_get(0).unwrap();
+                        let counts = (1..=JUDGEMENT_COLUMNS.len())
+                            .map(|idx| {
+                                let count: i32 = row.try_get(idx).unwrap();
+                                count.try_into().unwrap()
+                            })
+                            .collect::<Vec<u32>>();
+                        let score: i32 = row.try_get(JUDGEMENT_COLUMNS.len() + 1).unwrap();
+                        let top_combo: i32 = row.try_get(JUDGEMENT_COLUMNS.len() + 2).unwrap();
+                        let mods: String = row.try_get(JUDGEMENT_COLUMNS.len() + 3).unwrap();
+                        let model: String = row.try_get(JUDGEMENT_COLUMNS.len() + 4).unwrap();
+                        let grade: Option<String> =
+                            row.try_get(JUDGEMENT_COLUMNS.len() + 5).unwrap();
+                        let pp: f32 = row.try_get(JUDGEMENT_COLUMNS.len() + 6).unwrap();
+                        let judgements = score::judgements_from_counts(&counts);
+                        let mods: Vec<Mod> = serde_json::from_str(&mods).unwrap();
+                        CatchScore {
+                            username: Some(username),
+                            diff_hash: diff_hash.clone(),
+                            score: score.try_into().unwrap(),
+                            top_combo: top_combo.try_into().unwrap(),
+                            passed: true,
+                            model: ScoringModel::from_key(&model).unwrap_or_default(),
+                            // Scores submitted before grades were stored don't have one.
+                            grade: grade
+                                .as_deref()
+                                .and_then(Grade::from_key)
+                                .unwrap_or_else(|| Grade::from_judgements(&judgements, &mods)),
+                            judgements,
+                            mods,
+                            pp,
+                        }
+                    })
+                    .fetch_all(&self.app.pool)
+                    .await
+                    .unwrap();
+                self.app.send(
+                    Target::User(self.username.clone()),
+                    ServerPacket::Leaderboard { diff_hash, scores },
+                );
+            }
+            ClientPacket::RequestRanking => {
+                let ranking = self.ranking().await;
                 self.app.send(
                     Target::User(self.username.clone()),
-                    ServerPacket::Leaderboard { diff_id, scores },
+                    ServerPacket::Ranking(ranking),
                 );
             }
             ClientPacket::Goodbye => todo!(),
         }
     }
+
+    /// Every player ordered by total performance.
+    ///
+    /// Only the best play on each difficulty counts, and each one is worth 5% less than the one before it.
+    async fn ranking(&self) -> Vec<RankingEntry> {
+        sqlx::query_as(
+            "
+            SELECT username, SUM(pp * POWER(0.95::float8, idx - 1)) AS total
+                FROM (
+                    SELECT user_id, pp, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY pp DESC) AS idx
+                        FROM (SELECT user_id, MAX(pp) AS pp FROM scores GROUP BY user_id, COALESCE(diff_hash, diff_id::text)) best
+                ) weighted
+                INNER JOIN users ON (users.user_id = weighted.user_id)
+                GROUP BY username
+                ORDER BY total DESC
+                ",
+        )
+        .fetch_all(&self.app.pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(username, pp): (String, f64)| RankingEntry {
+            username,
+            pp: pp as f32,
+        })
+        .collect()
+    }
 }
@@ -0,0 +1,482 @@
+//! Converting osu! beatmap sets to chart folders, shared by the client and the importer so the
+//! importer doesn't have to build the game.
+
+use std::{
+    io::{Cursor, Read},
+    path::Path,
+};
+
+/// Formats of music the client can play.
+pub const AUDIO_FORMATS: &[&str] = &[
+    "wav",
+    #[cfg(feature = "ogg")]
+    "ogg",
+    #[cfg(feature = "mp3")]
+    "mp3",
+];
+
+/// Lists the files of a chart folder. Folders without one aren't charts.
+pub const MANIFEST: &str = "files.json";
+
+/// Lists the chart folders of the library on web, where folders can't be listed.
+pub const INDEX: &str = "charts.json";
+
+/// Replaces characters that aren't allowed in file names, for names of chart folders, difficulties and replays.
+pub fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Name of the music among the `files` of a chart folder. It's called `audio`, in any of the [`AUDIO_FORMATS`].
+pub fn audio_file(files: &[String]) -> Option<&str> {
+    files.iter().map(String::as_str).find(|file| {
+        matches!(file.rsplit_once('.'), Some(("audio", format)) if AUDIO_FORMATS.contains(&format))
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("couldn't read the archive: {0}")]
+    Archive(#[from] zip::result::ZipError),
+    #[error("no difficulty can be played")]
+    NoDifficulties,
+    #[error("{0} is missing")]
+    MissingFile(String),
+    #[error("{0} music isn't supported")]
+    UnsupportedAudio(String),
+    #[error("couldn't write the chart: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("couldn't store the chart: {0}")]
+    Storage(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A file of a beatmap set or chart folder.
+#[derive(Debug, Clone)]
+pub struct File {
+    /// Path relative to the set, with `/` as separator.
+    pub name: String,
+    pub content: Vec<u8>,
+}
+
+/// A beatmap set converted to the files of a chart folder.
+#[derive(Debug, Clone)]
+pub struct ChartFolder {
+    /// Name of the folder. [`install`] numbers it if another set already has it.
+    pub title: String,
+    /// Difficulties, music and background, without the manifest.
+    pub files: Vec<File>,
+    /// `.osu` files that couldn't be played.
+    pub skipped: Vec<String>,
+    /// Why the background was left out, if it couldn't be converted.
+    pub background_error: Option<String>,
+    pub set: SetIdentity,
+}
+
+/// What tells beatmap sets apart, read from one of their `.osu` files.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SetIdentity {
+    /// `None` for sets that were never uploaded.
+    pub set_id: Option<u32>,
+    pub artist: String,
+    pub title: String,
+    /// Name of the music file in the set.
+    pub audio: String,
+}
+
+/// Value of `key` in an `.osu` file, empty if it's missing. Every key is only in one section, so
+/// there's no need to find the sections.
+fn osu_value(osu: &str, key: &str) -> String {
+    osu.lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            (name.trim() == key).then(|| value.trim().to_owned())
+        })
+        .unwrap_or_default()
+}
+
+/// Whether a beatmap of this `Mode` can be converted to a chart. Catch beatmaps and osu! beatmaps,
+/// the default mode, can be, taiko and mania beatmaps can't.
+fn convertible_mode(mode: &str) -> bool {
+    matches!(mode, "" | "0" | "2")
+}
+
+impl SetIdentity {
+    pub fn new(osu: &[u8]) -> Self {
+        let osu = String::from_utf8_lossy(osu);
+        let value = |key: &str| osu_value(&osu, key);
+        SetIdentity {
+            // Sets that were never uploaded have -1 or no id.
+            set_id: value("BeatmapSetID").parse().ok().filter(|&id| id > 0),
+            artist: value("Artist"),
+            title: value("Title"),
+            audio: value("AudioFilename"),
+        }
+    }
+
+    /// Sets are compared by id when both have one.
+    pub fn same_set(&self, other: &SetIdentity) -> bool {
+        match (self.set_id, other.set_id) {
+            (Some(id), Some(other_id)) => id == other_id,
+            _ => {
+                self.artist == other.artist
+                    && self.title == other.title
+                    && self.audio.eq_ignore_ascii_case(&other.audio)
+            }
+        }
+    }
+}
+
+/// Files of an `.osz` archive.
+pub fn unpack_osz(content: &[u8]) -> Result<Vec<File>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(content))?;
+    let mut files = Vec::with_capacity(archive.len());
+    for idx in 0..archive.len() {
+        let mut entry = archive.by_index(idx)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let mut content = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut content)?;
+        files.push(File {
+            name: entry.name().replace('\\', "/"),
+            content,
+        });
+    }
+    Ok(files)
+}
+
+/// Lowercase extension of a file name, empty if it has none.
+fn extension(name: &str) -> String {
+    std::path::Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Beatmaps refer to files case insensitively, the way osu! finds them on Windows.
+fn same_file(name: &str, other: &str) -> bool {
+    let normalize = |name: &str| name.trim().replace('\\', "/");
+    normalize(name).eq_ignore_ascii_case(&normalize(other))
+}
+
+fn find<'a>(files: &'a [File], name: &str) -> Option<&'a File> {
+    files.iter().find(|file| same_file(&file.name, name))
+}
+
+/// The music most of `names` refer to, the first of them on a tie.
+fn shared_audio<'a>(names: &[&'a str]) -> Option<&'a str> {
+    names
+        .iter()
+        .rev()
+        .max_by_key(|name| names.iter().filter(|other| same_file(name, other)).count())
+        .copied()
+}
+
+/// Converts the files of a beatmap set to a chart folder. Difficulties that can't be played, or that
+/// use other music than most of the set, are skipped.
+pub fn convert_set(files: &[File]) -> Result<ChartFolder> {
+    let mut parsed = Vec::new();
+    let mut skipped = Vec::new();
+    for file in files.iter().filter(|file| extension(&file.name) == "osu") {
+        let beatmap = std::str::from_utf8(&file.content)
+            .ok()
+            .filter(|content| convertible_mode(&osu_value(content, "Mode")))
+            .and_then(|content| {
+                osu_parser::load_content(content, osu_parser::BeatmapParseOptions::default()).ok()
+            });
+        let beatmap = match beatmap {
+            // Every hit object becomes at least one fruit.
+            Some(beatmap) if !beatmap.hit_objects.is_empty() => beatmap,
+            _ => {
+                skipped.push(file.name.clone());
+                continue;
+            }
+        };
+        parsed.push((file, beatmap));
+    }
+
+    // A chart folder has one music file.
+    let audio_name = shared_audio(
+        &parsed
+            .iter()
+            .map(|(_, beatmap)| beatmap.info.general_data.audio_file_name.as_str())
+            .collect::<Vec<_>>(),
+    )
+    .ok_or(Error::NoDifficulties)?
+    .to_owned();
+    let mut folder = Vec::new();
+    let mut beatmaps = Vec::new();
+    for (file, beatmap) in parsed {
+        // The client finds difficulties by their name, so it has to be unique.
+        let name = format!("{}.osu", sanitize(&beatmap.info.metadata.version));
+        if !same_file(&beatmap.info.general_data.audio_file_name, &audio_name)
+            || folder.iter().any(|other: &File| other.name == name)
+        {
+            skipped.push(file.name.clone());
+            continue;
+        }
+        folder.push(File {
+            name,
+            content: file.content.clone(),
+        });
+        beatmaps.push(beatmap);
+    }
+
+    let first = beatmaps.first().ok_or(Error::NoDifficulties)?;
+    let audio = find(files, &audio_name).ok_or_else(|| Error::MissingFile(audio_name.clone()))?;
+    let format = extension(&audio.name);
+    if !AUDIO_FORMATS.contains(&format.as_str()) {
+        return Err(Error::UnsupportedAudio(format));
+    }
+    folder.push(File {
+        name: format!("audio.{}", format),
+        content: audio.content.clone(),
+    });
+
+    // Not every difficulty has a background, and it isn't always the first event.
+    let background = beatmaps
+        .iter()
+        .flat_map(|beatmap| &beatmap.events)
+        .find_map(|event| match event {
+            osu_types::Event::Background { filename, .. } => find(files, filename),
+            _ => None,
+        });
+    // A chart plays fine without its background, so one that can't be decoded doesn't fail the set.
+    let mut background_error = None;
+    if let Some(background) = background {
+        let mut png = Vec::new();
+        match image::load_from_memory(&background.content)
+            .and_then(|image| image.write_to(&mut png, image::ImageOutputFormat::Png))
+        {
+            Ok(()) => folder.push(File {
+                name: "bg.png".to_owned(),
+                content: png,
+            }),
+            Err(e) => background_error = Some(format!("{}: {}", background.name, e)),
+        }
+    }
+
+    Ok(ChartFolder {
+        title: sanitize(&first.info.metadata.title),
+        // The first file is the first difficulty.
+        set: SetIdentity::new(&folder[0].content),
+        files: folder,
+        skipped,
+        background_error,
+    })
+}
+
+/// `title`, numbered from the second folder with that title on, e.g. "title (2)".
+pub fn numbered(title: &str, idx: usize) -> String {
+    if idx == 1 {
+        title.to_owned()
+    } else {
+        format!("{} ({})", title, idx)
+    }
+}
+
+/// Whether `folder` can be added to the chart folder listing `manifest`, whose first difficulty is
+/// `existing`. Only the same set with the same music can share a folder, a folder has one music file.
+pub fn can_merge(folder: &ChartFolder, manifest: &[String], existing: Option<&[u8]>) -> bool {
+    if manifest.is_empty() {
+        return true;
+    }
+    let names = folder
+        .files
+        .iter()
+        .map(|file| file.name.clone())
+        .collect::<Vec<_>>();
+    existing.map_or(false, |existing| {
+        SetIdentity::new(existing).same_set(&folder.set)
+    }) && audio_file(manifest) == audio_file(&names)
+}
+
+/// First difficulty listed in a manifest.
+pub fn first_difficulty(manifest: &[String]) -> Option<&String> {
+    manifest.iter().find(|name| name.ends_with(".osu"))
+}
+
+/// Writes `folder` to the library folder at `library_path`. A chart folder of the same set gets the
+/// new difficulties added to it, other sets with the same title get a numbered folder. Files that are
+/// already there are never overwritten, and are left out of `folder.files`.
+pub fn install(folder: &mut ChartFolder, library_path: &Path) -> Result<()> {
+    let mut idx = 1;
+    loop {
+        let title = numbered(&folder.title, idx);
+        idx += 1;
+        let path = library_path.join(&title);
+        let manifest = match std::fs::read(path.join(MANIFEST)) {
+            Ok(content) => serde_json::from_slice::<Vec<String>>(&content).unwrap_or_default(),
+            // Not a chart folder, but its files aren't ours to overwrite either.
+            Err(_) if path.exists() => continue,
+            Err(_) => Vec::new(),
+        };
+        let existing =
+            first_difficulty(&manifest).and_then(|name| std::fs::read(path.join(name)).ok());
+        if !can_merge(folder, &manifest, existing.as_deref()) {
+            continue;
+        }
+
+        std::fs::create_dir_all(&path)?;
+        folder.title = title;
+        folder
+            .files
+            .retain(|file| !manifest.contains(&file.name) && !path.join(&file.name).exists());
+        let mut manifest = manifest;
+        for file in &folder.files {
+            std::fs::write(path.join(&file.name), &file.content)?;
+            manifest.push(file.name.clone());
+        }
+        std::fs::write(
+            path.join(MANIFEST),
+            serde_json::to_string_pretty(&manifest).unwrap(),
+        )?;
+        return Ok(());
+    }
+}
+
+/// Beatmap sets in picked files: every `.osz` is one, and the other files together are one, like a folder.
+pub fn picked_sets(files: Vec<File>) -> Vec<Result<Vec<File>>> {
+    let (archives, loose): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|file| extension(&file.name) == "osz");
+    let mut sets = archives
+        .iter()
+        .map(|archive| unpack_osz(&archive.content))
+        .collect::<Vec<_>>();
+    if !loose.is_empty() {
+        sets.push(Ok(loose));
+    }
+    sets
+}
+
+#[test]
+fn test_sanitize() {
+    assert_eq!(sanitize("a/b: c?"), "a_b_ c_");
+    assert_eq!(sanitize("Kizuato - Platter"), "Kizuato - Platter");
+}
+
+#[test]
+fn test_audio_file() {
+    let files = |names: &[&str]| {
+        names
+            .iter()
+            .map(|&name| name.to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        audio_file(&files(&["Platter.osu", "audio.wav", "bg.png"])),
+        Some("audio.wav")
+    );
+    assert_eq!(audio_file(&files(&["audio.flac", "bg.png"])), None);
+    assert_eq!(audio_file(&files(&["other.wav"])), None);
+}
+
+#[test]
+fn test_picked_sets() {
+    let file = |name: &str| File {
+        name: name.to_owned(),
+        content: Vec::new(),
+    };
+    // Empty archives can't be read.
+    let sets = picked_sets(vec![file("a.osz"), file("b.osu"), file("audio.wav")]);
+    assert_eq!(sets.len(), 2);
+    assert!(sets[0].is_err());
+    assert_eq!(sets[1].as_ref().unwrap().len(), 2);
+    assert!(picked_sets(Vec::new()).is_empty());
+}
+
+#[test]
+fn test_find() {
+    let files = [File {
+        name: "Audio.WAV".to_owned(),
+        content: Vec::new(),
+    }];
+    assert!(find(&files, " audio.wav").is_some());
+    assert!(find(&files, "bg.png").is_none());
+    assert_eq!(
+        shared_audio(&["a.mp3", "b.mp3", "B.mp3", "a.mp3"]),
+        Some("a.mp3")
+    );
+    assert_eq!(shared_audio(&["a.mp3", "b.mp3", " B.mp3"]), Some("b.mp3"));
+    assert_eq!(shared_audio(&[]), None);
+    assert_eq!(extension("sb/Kizuato.OSU"), "osu");
+    assert_eq!(extension("audio"), "");
+}
+
+#[test]
+fn test_mode() {
+    let mode = |osu: &str| convertible_mode(&osu_value(osu, "Mode"));
+    assert!(mode("[General]\nAudioFilename: audio.mp3\n"));
+    assert!(mode("[General]\nMode: 0\n"));
+    assert!(mode("[General]\nMode: 2\n"));
+    assert!(!mode("[General]\nMode: 1\n"));
+    assert!(!mode("[General]\nMode:3\n"));
+}
+
+#[test]
+fn test_can_merge() {
+    let osu = |set_id: i32, audio: &str| {
+        format!(
+            "osu file format v14\n\n[General]\nAudioFilename: {}\n\n[Metadata]\nTitle:Kizuato\nArtist:Kuba Oms\nBeatmapSetID:{}\n",
+            audio, set_id
+        )
+        .into_bytes()
+    };
+    let folder = |set_id: i32, audio: &str| ChartFolder {
+        title: "Kizuato".to_owned(),
+        files: vec![File {
+            name: "audio.wav".to_owned(),
+            content: Vec::new(),
+        }],
+        skipped: Vec::new(),
+        background_error: None,
+        set: SetIdentity::new(&osu(set_id, audio)),
+    };
+    let manifest = ["Platter.osu".to_owned(), "audio.wav".to_owned()];
+
+    assert_eq!(folder(5, "a.wav").set.set_id, Some(5));
+    assert_eq!(folder(-1, "a.wav").set.set_id, None);
+    assert_eq!(folder(5, "a.wav").set.audio, "a.wav");
+
+    assert!(can_merge(&folder(5, "a.wav"), &[], None));
+    assert!(can_merge(
+        &folder(5, "a.wav"),
+        &manifest,
+        Some(&osu(5, "b.wav")[..])
+    ));
+    assert!(!can_merge(
+        &folder(5, "a.wav"),
+        &manifest,
+        Some(&osu(6, "a.wav")[..])
+    ));
+    assert!(!can_merge(&folder(5, "a.wav"), &manifest, None));
+    // Without ids, sets are told apart by their metadata and music.
+    assert!(can_merge(
+        &folder(-1, "a.wav"),
+        &manifest,
+        Some(&osu(-1, "A.wav")[..])
+    ));
+    assert!(!can_merge(
+        &folder(-1, "a.wav"),
+        &manifest,
+        Some(&osu(-1, "b.wav")[..])
+    ));
+    // A folder only has one music file.
+    let mp3 = ["Platter.osu".to_owned(), "audio.mp3".to_owned()];
+    assert!(!can_merge(
+        &folder(5, "a.wav"),
+        &mp3,
+        Some(&osu(5, "a.wav")[..])
+    ));
+
+    assert_eq!(numbered("Kizuato", 1), "Kizuato");
+    assert_eq!(numbered("Kizuato", 2), "Kizuato (2)");
+}
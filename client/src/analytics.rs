@@ -0,0 +1,60 @@
+use crate::{rulesets::JudgementResult, score::Judgement};
+
+/// State of a play right after an object was judged.
+#[derive(Debug, Clone)]
+pub struct HitEvent<J: Judgement> {
+    /// Chart time of the object, in seconds.
+    pub time: f32,
+    pub judgement: JudgementResult<J>,
+    /// How far from the center of the catcher the object was caught \[-1; 1\], if it was caught.
+    pub offset: Option<f32>,
+    pub hp: f32,
+    pub combo: u32,
+    pub accuracy: f32,
+}
+
+/// Counts the catch offsets of `events` in `bins` equally wide bins from -1 to 1.
+pub fn offset_histogram<J: Judgement>(events: &[HitEvent<J>], bins: usize) -> Vec<u32> {
+    let mut histogram = vec![0; bins];
+    for offset in events.iter().filter_map(|event| event.offset) {
+        let bin = ((offset + 1.) / 2. * bins as f32) as usize;
+        histogram[bin.min(bins - 1)] += 1;
+    }
+    histogram
+}
+
+/// Times of the objects that broke combo.
+pub fn miss_times<J: Judgement>(events: &[HitEvent<J>]) -> Vec<f32> {
+    events
+        .iter()
+        .filter(|event| event.judgement == JudgementResult::Miss)
+        .map(|event| event.time)
+        .collect()
+}
+
+#[test]
+fn test_offset_histogram() {
+    use crate::rulesets::catch::CatchJudgement;
+
+    let event = |offset: Option<f32>| HitEvent {
+        time: 0.,
+        judgement: match offset {
+            Some(_) => JudgementResult::Hit(CatchJudgement::Fruit),
+            None => JudgementResult::Miss,
+        },
+        offset,
+        hp: 1.,
+        combo: 0,
+        accuracy: 1.,
+    };
+    let events = [
+        event(Some(-1.)),
+        event(Some(-0.6)),
+        event(Some(0.)),
+        event(Some(0.1)),
+        event(Some(1.)),
+        event(None),
+    ];
+    assert_eq!(offset_histogram(&events, 4), vec![2, 0, 2, 1]);
+    assert_eq!(miss_times(&events), vec![0.]);
+}
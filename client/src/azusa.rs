@@ -1,4 +1,5 @@
 use crate::chat::ChatMessagePacket;
+use crate::identity::DifficultyHash;
 use crate::rulesets::catch::CatchScore;
 use crate::web_socket::{ConnectionStatus, WebSocket};
 use crate::LogType;
@@ -19,9 +20,19 @@ pub enum ServerPacket {
     Connected { version: String },
     /// Response to [`ClientPacket::RequestLeaderboard`]
     Leaderboard {
-        diff_id: u32,
+        diff_hash: DifficultyHash,
         scores: Vec<CatchScore>,
     },
+    /// Response to [`ClientPacket::RequestRanking`], players ordered by total performance.
+    Ranking(Vec<RankingEntry>),
+}
+
+/// A player on the global performance ranking.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankingEntry {
+    pub username: String,
+    /// Weighted sum of the player's best performance on every difficulty.
+    pub pp: f32,
 }
 
 /// Packet sent from the game client, towards Azusa
@@ -37,8 +48,10 @@ pub enum ClientPacket {
     Login(uuid::Uuid),
     /// Submit a score to the leaderboard
     Submit(CatchScore),
-    /// Request the leaderboard for given difficulty id. Reponse given via [`ServerPacket::Leaderboard`]
-    RequestLeaderboard(u32),
+    /// Request the leaderboard for given difficulty. Reponse given via [`ServerPacket::Leaderboard`]
+    RequestLeaderboard(DifficultyHash),
+    /// Request the global performance ranking. Reponse given via [`ServerPacket::Ranking`]
+    RequestRanking,
     /// Inform Azusa we are quitting
     Goodbye,
 }
@@ -1,3 +1,4 @@
+use crate::library;
 use kira::{
     sound::static_sound::{StaticSoundData, StaticSoundSettings},
     track::TrackId,
@@ -147,13 +148,16 @@ impl Cache<StaticSoundData> {
     ) -> Result<StaticSoundData, LoadError> {
         let res = self
             .get(path, move || async move {
-                let sound_data = load_file(path).await.map_err(LoadError::FileError)?;
+                let sound_data = library::load_file(path)
+                    .await
+                    .map_err(LoadError::FileError)?;
+                // Fails for formats whose decoder isn't enabled.
                 WaitForBlockingFuture::new(move || {
-                    Ok(StaticSoundData::from_cursor(
+                    StaticSoundData::from_cursor(
                         Cursor::new(sound_data),
                         StaticSoundSettings::default().track(track),
                     )
-                    .unwrap())
+                    .map_err(|_| LoadError::Generic)
                 })
                 .await
             })
@@ -178,7 +182,10 @@ impl Cache<StaticSoundData> {
 impl Cache<Texture2D> {
     pub async fn get_texture(&self, path: &str) -> Texture2D {
         let res = self
-            .get(path, || async { Ok(load_texture(path).await.unwrap()) })
+            .get(path, || async {
+                let content = library::load_file(path).await.unwrap();
+                Ok(Texture2D::from_file_with_format(&content, None))
+            })
             .await;
         *res.unwrap()
     }
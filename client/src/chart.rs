@@ -8,13 +8,33 @@ pub struct Additions {
     pub clap: bool,
 }
 
+/// The different kinds of catch objects.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FruitKind {
+    /// Circles and slider heads and tails.
+    Fruit,
+    /// Slider ticks.
+    Droplet,
+    /// Fill the gaps between droplets, they don't affect combo.
+    TinyDroplet,
+    /// Created from spinners, they don't affect combo or accuracy.
+    Banana,
+}
+
+impl FruitKind {
+    /// Whether catching (or missing) this kind of object changes the combo.
+    pub fn affects_combo(&self) -> bool {
+        matches!(self, FruitKind::Fruit | FruitKind::Droplet)
+    }
+}
+
 /// Represents a catch fruit.
 #[derive(Debug, Copy, Clone)]
 pub struct Fruit {
     pub position: f32,
     pub time: f32,
     pub hyper: Option<f32>,
-    pub small: bool,
+    pub kind: FruitKind,
     pub additions: Additions,
     pub color: Color,
     pub plate_reset: bool,
@@ -41,22 +61,35 @@ pub enum HitSoundKind {
     Custom(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum EventData {
     Timing { bpm: f32 },
     Hitsound { kind: HitSoundKind, volume: f32 },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Event {
     pub time: f32,
     pub data: EventData,
 }
 
+#[derive(Clone)]
 pub struct Chart {
     pub fruits: Vec<Fruit>,
     pub events: Vec<Event>,
     pub fall_time: f32,
     pub fruit_radius: f32,
     pub catcher_width: f32,
+    /// HP drain setting of the beatmap, range \[0; 10\].
+    pub hp_drain: f32,
+}
+
+impl Chart {
+    /// Highest combo possible, only fruits and droplets count towards combo.
+    pub fn max_combo(&self) -> u32 {
+        self.fruits
+            .iter()
+            .filter(|fruit| fruit.kind.affects_combo())
+            .count() as u32
+    }
 }
@@ -1,6 +1,6 @@
 use macroquad::prelude::*;
-use serde::ser::SerializeMap;
-use std::intrinsics::transmute;
+use serde::{ser::SerializeMap, Deserialize};
+use std::collections::BTreeMap;
 
 /// Sets a global configuration value. Uses local sotrage on web and "data/config.json" on native.
 pub fn set_value<T: serde::Serialize>(key: &str, value: T) {
@@ -65,69 +65,362 @@ pub fn get_value<T: serde::de::DeserializeOwned>(key: &str) -> Option<T> {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct KeyBinds {
-    pub right: KeyCode,
-    pub left: KeyCode,
-    pub dash: KeyCode,
+/// Every [`KeyCode`], used to look keys up by name.
+const KEY_CODES: [KeyCode; 120] = [
+    KeyCode::Space,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Semicolon,
+    KeyCode::Equal,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::LeftBracket,
+    KeyCode::Backslash,
+    KeyCode::RightBracket,
+    KeyCode::GraveAccent,
+    KeyCode::World1,
+    KeyCode::World2,
+    KeyCode::Escape,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Right,
+    KeyCode::Left,
+    KeyCode::Down,
+    KeyCode::Up,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::CapsLock,
+    KeyCode::ScrollLock,
+    KeyCode::NumLock,
+    KeyCode::PrintScreen,
+    KeyCode::Pause,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::F13,
+    KeyCode::F14,
+    KeyCode::F15,
+    KeyCode::F16,
+    KeyCode::F17,
+    KeyCode::F18,
+    KeyCode::F19,
+    KeyCode::F20,
+    KeyCode::F21,
+    KeyCode::F22,
+    KeyCode::F23,
+    KeyCode::F24,
+    KeyCode::F25,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpDecimal,
+    KeyCode::KpDivide,
+    KeyCode::KpMultiply,
+    KeyCode::KpSubtract,
+    KeyCode::KpAdd,
+    KeyCode::KpEnter,
+    KeyCode::KpEqual,
+    KeyCode::LeftShift,
+    KeyCode::LeftControl,
+    KeyCode::LeftAlt,
+    KeyCode::LeftSuper,
+    KeyCode::RightShift,
+    KeyCode::RightControl,
+    KeyCode::RightAlt,
+    KeyCode::RightSuper,
+    KeyCode::Menu,
+];
+
+/// Readable name of `key`, used in the config and shown in the key binding editor.
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
 }
 
-impl serde::Serialize for KeyBinds {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let mut map = serializer.serialize_map(Some(3))?;
-        map.serialize_entry("left", &(self.left as u32))?;
-        map.serialize_entry("right", &(self.right as u32))?;
-        map.serialize_entry("dash", &(self.dash as u32))?;
-        map.end()
+/// Inverse of [`key_name`].
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEY_CODES.into_iter().find(|&key| key_name(key) == name)
+}
+
+/// Something the player can do with a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    Left,
+    Right,
+    Dash,
+    Pause,
+    Retry,
+    Skip,
+    Chat,
+    Settings,
+    Ranking,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Left,
+        Action::Right,
+        Action::Dash,
+        Action::Pause,
+        Action::Retry,
+        Action::Skip,
+        Action::Chat,
+        Action::Settings,
+        Action::Ranking,
+    ];
+
+    /// Display name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Dash => "Dash",
+            Action::Pause => "Pause",
+            Action::Retry => "Retry",
+            Action::Skip => "Skip",
+            Action::Chat => "Chat",
+            Action::Settings => "Settings",
+            Action::Ranking => "Ranking",
+        }
+    }
+
+    /// Identifier used when storing bindings in the config.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Dash => "dash",
+            Action::Pause => "pause",
+            Action::Retry => "retry",
+            Action::Skip => "skip",
+            Action::Chat => "chat",
+            Action::Settings => "settings",
+            Action::Ranking => "ranking",
+        }
+    }
+
+    /// Inverse of [`Self::key`].
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.key() == key)
     }
 }
 
-/// [`macroquad::KeyCode`] doesn't implement [`serde::Deserialize`] or [`serde::Serialize`]..
-struct KeyBindVisitor;
+/// Keys bound to each [`Action`]. An action can have any number of keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBinds {
+    keys: BTreeMap<Action, Vec<KeyCode>>,
+}
 
-impl<'de> serde::de::Visitor<'de> for KeyBindVisitor {
-    type Value = KeyBinds;
+impl KeyBinds {
+    fn with_movement(left: KeyCode, right: KeyCode, dash: KeyCode) -> Self {
+        KeyBinds {
+            keys: BTreeMap::from([
+                (Action::Left, vec![left]),
+                (Action::Right, vec![right]),
+                (Action::Dash, vec![dash]),
+                (Action::Pause, vec![KeyCode::Escape]),
+                (Action::Retry, vec![KeyCode::GraveAccent]),
+                (Action::Skip, vec![KeyCode::Space]),
+                (Action::Chat, vec![KeyCode::F9]),
+                (Action::Settings, vec![KeyCode::F1]),
+                (Action::Ranking, vec![KeyCode::F4]),
+            ]),
+        }
+    }
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("Expected a map with the keys 'left', 'right', and 'dash'")
+    /// A D RShift
+    pub fn left_handed() -> Self {
+        KeyBinds::with_movement(KeyCode::A, KeyCode::D, KeyCode::RightShift)
     }
 
-    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-    where
-        A: serde::de::MapAccess<'de>,
-    {
-        let mut binds = KeyBinds {
-            right: KeyCode::D,
-            left: KeyCode::A,
-            dash: KeyCode::RightShift,
-        };
-        while let Some((key, value)) = map.next_entry::<_, u32>()? {
-            let value = unsafe { transmute(value) };
-            match key {
-                "left" => {
-                    binds.left = value;
-                }
-                "right" => {
-                    binds.right = value;
-                }
-                "dash" => {
-                    binds.dash = value;
+    /// Left Right LShift
+    pub fn right_handed() -> Self {
+        KeyBinds::with_movement(KeyCode::Left, KeyCode::Right, KeyCode::LeftShift)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn set_keys(&mut self, action: Action, keys: Vec<KeyCode>) {
+        self.keys.insert(action, keys);
+    }
+
+    pub fn is_down(&self, action: Action) -> bool {
+        self.keys(action).iter().any(|&key| is_key_down(key))
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.keys(action).iter().any(|&key| is_key_pressed(key))
+    }
+
+    /// Keys bound to more than one action, with the two actions sharing it.
+    pub fn conflicts(&self) -> Vec<(KeyCode, Action, Action)> {
+        let mut conflicts = Vec::new();
+        for (idx, &a) in Action::ALL.iter().enumerate() {
+            for &b in &Action::ALL[idx + 1..] {
+                for &key in self.keys(a) {
+                    if self.keys(b).contains(&key) {
+                        conflicts.push((key, a, b));
+                    }
                 }
-                _ => panic!(),
             }
         }
-        Ok(binds)
+        conflicts
+    }
+}
+
+impl Default for KeyBinds {
+    fn default() -> Self {
+        KeyBinds::left_handed()
+    }
+}
+
+impl serde::Serialize for KeyBinds {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.keys.len()))?;
+        for (action, keys) in &self.keys {
+            let names = keys.iter().map(|&key| key_name(key)).collect::<Vec<_>>();
+            map.serialize_entry(action.key(), &names)?;
+        }
+        map.end()
     }
 }
 
+/// Keys of an action as stored in the config.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredKeys {
+    Names(Vec<String>),
+    /// Older configs stored a single [`KeyCode`] as a number.
+    Code(u32),
+}
+
 impl<'de> serde::Deserialize<'de> for KeyBinds {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_map(KeyBindVisitor)
+        // Actions missing from the config keep their default keys.
+        let mut binds = KeyBinds::default();
+        for (action, keys) in BTreeMap::<String, StoredKeys>::deserialize(deserializer)? {
+            let action = match Action::from_key(&action) {
+                Some(action) => action,
+                None => continue,
+            };
+            let keys = match keys {
+                StoredKeys::Names(names) => names
+                    .iter()
+                    .filter_map(|name| key_from_name(name))
+                    .collect(),
+                StoredKeys::Code(code) => KEY_CODES
+                    .into_iter()
+                    .filter(|&key| key as u32 == code)
+                    .collect(),
+            };
+            binds.set_keys(action, keys);
+        }
+        Ok(binds)
+    }
+}
+
+#[test]
+fn test_key_names() {
+    for key in KEY_CODES {
+        assert_eq!(key_from_name(&key_name(key)), Some(key));
     }
+    assert_eq!(key_name(KeyCode::RightShift), "RightShift");
+    assert_eq!(key_from_name("NotAKey"), None);
+}
+
+#[test]
+fn test_key_binds_serde() {
+    let mut binds = KeyBinds::right_handed();
+    binds.set_keys(Action::Dash, vec![KeyCode::LeftShift, KeyCode::Z]);
+    let value = serde_json::to_value(&binds).unwrap();
+    assert_eq!(value["dash"], serde_json::json!(["LeftShift", "Z"]));
+    assert_eq!(serde_json::from_value::<KeyBinds>(value).unwrap(), binds);
+
+    // Older configs stored one key code per action, and only for movement.
+    let legacy = serde_json::json!({
+        "left": KeyCode::Left as u32,
+        "right": KeyCode::Right as u32,
+        "dash": KeyCode::LeftShift as u32,
+    });
+    assert_eq!(
+        serde_json::from_value::<KeyBinds>(legacy).unwrap(),
+        KeyBinds::right_handed()
+    );
+}
+
+#[test]
+fn test_conflicts() {
+    let mut binds = KeyBinds::left_handed();
+    assert!(binds.conflicts().is_empty());
+
+    binds.set_keys(Action::Skip, vec![KeyCode::Space, KeyCode::RightShift]);
+    assert_eq!(
+        binds.conflicts(),
+        vec![(KeyCode::RightShift, Action::Dash, Action::Skip)]
+    );
 }
@@ -1,5 +1,5 @@
 use crate::{
-    chart::{Additions, Chart, Event, EventData, Fruit, HitSoundKind},
+    chart::{Additions, Chart, Event, EventData, Fruit, FruitKind, HitSoundKind},
     rulesets::catch::catcher_speed,
 };
 use macroquad::prelude::Color;
@@ -19,7 +19,7 @@ pub fn from_hit_sound_bits(bits: u8) -> Additions {
 /// Converts an osu [`osu_types::HitObject`] into a catch [`Fruit`]
 pub fn from_hitobject(
     hitobject: &osu_types::HitObject,
-    small: bool,
+    kind: FruitKind,
     color: Color,
     plate_reset: bool,
     fall_multiplier: f32,
@@ -28,7 +28,7 @@ pub fn from_hitobject(
         position: hitobject.position.0 as f32,
         time: hitobject.time as f32 / 1000.,
         hyper: None,
-        small,
+        kind,
         additions: from_hit_sound_bits(match &hitobject.specific {
             SpecificHitObject::Slider { edge_sounds, .. } => *edge_sounds.first().unwrap(),
             _ => hitobject.hit_sound,
@@ -103,7 +103,7 @@ impl ConvertFrom<osu_parser::Beatmap> for Chart {
 
             let fruit = from_hitobject(
                 hitobject,
-                false,
+                FruitKind::Fruit,
                 Color {
                     r: color.r as f32 / u8::MAX as f32,
                     g: color.g as f32 / u8::MAX as f32,
@@ -113,6 +113,39 @@ impl ConvertFrom<osu_parser::Beatmap> for Chart {
                 !is_first && hitobject.new_combo,
                 opx_per_sec / 432.5,
             );
+
+            // Spinners are replaced by a shower of bananas.
+            if let osu_types::SpecificHitObject::Spinner { end_time, .. } = &hitobject.specific {
+                let duration = *end_time as f32 / 1000. - fruit.time;
+
+                // Halve the spacing until there's at least one banana every 100ms.
+                let mut spacing = duration;
+                while spacing > 0.1 {
+                    spacing /= 2.;
+                }
+
+                let count = if spacing > 0. {
+                    (duration / spacing) as u32
+                } else {
+                    0
+                };
+                for i in 0..=count {
+                    let time = fruit.time + spacing * i as f32;
+                    // Positions need to be the same every time the chart is converted, for replays.
+                    let seed = ((time * 1000.) as u32).wrapping_mul(2654435761);
+                    fruits.push(Fruit {
+                        position: (seed % 512) as f32,
+                        time,
+                        kind: FruitKind::Banana,
+                        plate_reset: false,
+                        ..fruit
+                    });
+                }
+
+                is_first = false;
+                continue;
+            }
+
             fruits.push(fruit);
 
             // If it's a slider we need to create the drop and the slider tail fruit.
@@ -150,40 +183,54 @@ impl ConvertFrom<osu_parser::Beatmap> for Chart {
                 let secs_per_beat = 1.0 / bps;
                 let secs_per_drop = secs_per_beat / beatmap.info.difficulty.slider_tick_rate;
 
-                // Place a drop every `secs_per_drop` seconds.
+                // Place a drop every `secs_per_drop` seconds, followed by the slider tail fruit.
                 let drops = (slide_length_secs / secs_per_drop).floor() as u32;
-                for i in 1..drops {
-                    let sec = secs_per_drop * i as f32;
-                    let opx = opx_per_sec * sec;
-                    let position = spline.point_at_length(opx).x;
+                let nested = (1..drops)
+                    .map(|i| (secs_per_drop * i as f32, FruitKind::Droplet))
+                    .chain(std::iter::once((slide_length_secs, FruitKind::Fruit)));
+
+                let mut prev_sec = 0.;
+                for (sec, kind) in nested {
+                    // Fill the gap since the previous object with tiny droplets, at most 100ms apart.
+                    let mut tiny_spacing = sec - prev_sec;
+                    while tiny_spacing > 0.1 {
+                        tiny_spacing /= 2.;
+                    }
+                    let mut tiny_sec = prev_sec + tiny_spacing;
+                    while sec - tiny_sec > tiny_spacing / 2. {
+                        fruits.push(Fruit {
+                            position: spline.point_at_length(opx_per_sec * tiny_sec).x,
+                            time: fruit.time + tiny_sec,
+                            hyper: None,
+                            kind: FruitKind::TinyDroplet,
+                            plate_reset: false,
+                            ..fruit
+                        });
+                        tiny_sec += tiny_spacing;
+                    }
+
                     fruits.push(Fruit {
-                        position,
+                        position: spline.point_at_length(opx_per_sec * sec).x,
                         time: fruit.time + sec,
                         hyper: None,
-                        small: true,
+                        kind,
                         plate_reset: false,
                         ..fruit
-                    })
+                    });
+                    prev_sec = sec;
                 }
-
-                // Create slider tail fruit.
-                fruits.push(Fruit {
-                    position: spline.point_at_length(*length).x,
-                    time: fruit.time + slide_length_secs,
-                    hyper: None,
-                    small: false,
-                    plate_reset: false,
-                    ..fruit
-                })
             }
 
             is_first = false;
         }
 
-        // Calculate hyper speeds for each fruit.
-        for idx in 0..fruits.len().saturating_sub(1) {
-            let [fruit, next_fruit]: &mut [Fruit; 2] =
-                (&mut fruits[idx..idx + 2]).try_into().unwrap();
+        // Calculate hyper speeds for each fruit. Tiny droplets and bananas are ignored, like in osu!catch.
+        let combo_indices = (0..fruits.len())
+            .filter(|&idx| fruits[idx].kind.affects_combo())
+            .collect::<Vec<_>>();
+        for &[idx, next_idx] in combo_indices.array_windows::<2>() {
+            let next_fruit = fruits[next_idx];
+            let fruit = &mut fruits[idx];
             // If you can't get to the center of the next fruit in time, we need to give the player some extra speed.
             // TODO use same implementation as osu!catch.
             let dist = (next_fruit.position - fruit.position).abs();
@@ -254,6 +301,7 @@ impl ConvertFrom<osu_parser::Beatmap> for Chart {
                 let scale = 1. - 0.7 * (beatmap.info.difficulty.cs - 5.) / 5.;
                 106.75 * scale * 0.8
             },
+            hp_drain: beatmap.info.difficulty.hp,
         }
     }
 }
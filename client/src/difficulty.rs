@@ -0,0 +1,326 @@
+use crate::{
+    chart::{Chart, Fruit},
+    identity::DifficultyHash,
+    mods::{self, Mod},
+};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::HashMap, path::PathBuf, sync::Arc};
+#[cfg(not(target_family = "wasm"))]
+use {crate::LogType, aether::log};
+
+/// Bump whenever the calculation changes so stale cached results are recalculated.
+const VERSION: u32 = 2;
+
+/// Length of a section of the strain timeline, in seconds.
+pub const SECTION_LENGTH: f32 = 0.4;
+
+/// Shortest time between two objects taken into account, so stacked objects don't give infinite strain.
+const MIN_DELTA_TIME: f32 = 0.025;
+
+/// How much each section peak counts compared to the one before it when they are summed up.
+const DECAY_WEIGHT: f32 = 0.9;
+
+/// Stable hash of `content`, part of a [`DifficultyHash`].
+pub fn chart_hash(content: &[u8]) -> u64 {
+    // 64-bit FNV-1a.
+    content.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Result of a difficulty calculation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DifficultyAttributes {
+    pub star_rating: f32,
+    /// Difficulty of moving between fruits.
+    pub movement: f32,
+    /// Difficulty of switching between moving left and right.
+    pub direction_changes: f32,
+    /// Difficulty of the jumps that need a hyperdash.
+    pub hyperdash: f32,
+    /// Difficulty of how close the fruits are in time.
+    pub density: f32,
+    /// Highest combined strain of every [`SECTION_LENGTH`] long section, in time order.
+    pub strains: Vec<f32>,
+    /// Chart time covered by `strains`, in seconds.
+    pub length: f32,
+    pub max_combo: u32,
+    /// Time in seconds it takes a fruit to fall across the screen at normal speed.
+    pub fall_time: f32,
+}
+
+/// A strain that builds up with each object and decays over time.
+struct Skill {
+    /// Fraction of the strain left after one second.
+    decay: f32,
+    strain: f32,
+    peaks: Vec<f32>,
+}
+
+impl Skill {
+    fn new(decay: f32) -> Self {
+        Skill {
+            decay,
+            strain: 0.,
+            peaks: Vec::new(),
+        }
+    }
+
+    fn process(&mut self, value: f32, delta_time: f32, section: usize) -> f32 {
+        self.strain = self.strain * self.decay.powf(delta_time) + value;
+        if self.peaks.len() <= section {
+            self.peaks.resize(section + 1, 0.);
+        }
+        self.peaks[section] = self.peaks[section].max(self.strain);
+        self.strain
+    }
+
+    fn difficulty(&self) -> f32 {
+        weighted_sum(&self.peaks)
+    }
+}
+
+/// Sum of `peaks` with the hardest counting the most.
+fn weighted_sum(peaks: &[f32]) -> f32 {
+    let mut peaks = peaks.to_vec();
+    peaks.sort_by(|a, b| b.total_cmp(a));
+    peaks
+        .iter()
+        .enumerate()
+        .map(|(idx, peak)| peak * DECAY_WEIGHT.powi(idx as i32))
+        .sum()
+}
+
+// https://www.desmos.com/calculator/yt3tru6suf
+// \frac{1}{1+e^{\frac{v}{100}\left(x-m\right)}}
+fn density(diff_ms: f32) -> f32 {
+    const V: f32 = 3.0 / 100.;
+    const M: f32 = 166.0;
+    1. / (1. + (diff_ms * V - M * V).exp())
+}
+
+/// Calculates the difficulty of `chart` when played with `mods`.
+pub fn calculate(chart: &Chart, mods: &[Mod]) -> DifficultyAttributes {
+    let rate = mods::rate(mods);
+    // Tiny droplets and bananas don't need to be caught to keep the combo, so they don't add difficulty.
+    let fruits = chart
+        .fruits
+        .iter()
+        .filter(|fruit| fruit.kind.affects_combo())
+        .copied()
+        .collect::<Vec<Fruit>>();
+
+    let mut movement = Skill::new(0.15);
+    let mut direction_changes = Skill::new(0.1);
+    let mut hyperdash = Skill::new(0.1);
+    let mut density_skill = Skill::new(0.3);
+    let mut strains = Vec::new();
+
+    let mut prev_movement = 0.;
+    for [a, b] in fruits.array_windows::<2>() {
+        let delta_time = ((b.time - a.time) / rate).max(MIN_DELTA_TIME);
+        let section = (b.time / rate / SECTION_LENGTH).max(0.) as usize;
+        // Measured in catcher widths so charts with different circle sizes are comparable.
+        let distance = (b.position - a.position) / chart.catcher_width;
+
+        let movement_value = distance.abs() / delta_time * 0.1;
+        let direction_change_value = if distance.abs() > 0.1 && prev_movement * distance < 0. {
+            0.2 / delta_time.sqrt()
+        } else {
+            0.
+        };
+        let hyperdash_value = if a.hyper.is_some() { 0.5 } else { 0. };
+        let density_value = density(delta_time * 1000.);
+        if distance.abs() > 0.1 {
+            prev_movement = distance;
+        }
+
+        let strain = movement.process(movement_value, delta_time, section)
+            + direction_changes.process(direction_change_value, delta_time, section)
+            + hyperdash.process(hyperdash_value, delta_time, section)
+            + density_skill.process(density_value, delta_time, section);
+        if strains.len() <= section {
+            strains.resize(section + 1, 0.);
+        }
+        strains[section] = f32::max(strains[section], strain);
+    }
+
+    DifficultyAttributes {
+        star_rating: weighted_sum(&strains).sqrt(),
+        length: strains.len() as f32 * SECTION_LENGTH * rate,
+        movement: movement.difficulty(),
+        direction_changes: direction_changes.difficulty(),
+        hyperdash: hyperdash.difficulty(),
+        density: density_skill.difficulty(),
+        strains,
+        max_combo: chart.max_combo(),
+        fall_time: chart.fall_time,
+    }
+}
+
+/// [`calculate`] on another thread, so the frame isn't held up by long charts.
+#[cfg(not(target_family = "wasm"))]
+pub async fn calculate_async(
+    chart: impl FnOnce() -> Chart + Send + 'static,
+    mods: Vec<Mod>,
+) -> DifficultyAttributes {
+    let (tx, rx) = flume::bounded(1);
+    std::thread::spawn(move || {
+        let _ = tx.send(calculate(&chart(), &mods));
+    });
+    rx.recv_async().await.unwrap()
+}
+
+/// [`calculate`], there are no threads to run it on.
+#[cfg(target_family = "wasm")]
+pub async fn calculate_async(
+    chart: impl FnOnce() -> Chart + Send + 'static,
+    mods: Vec<Mod>,
+) -> DifficultyAttributes {
+    calculate(&chart(), &mods)
+}
+
+/// Only mods that change the difficulty are part of the cache key.
+fn cache_key(hash: &DifficultyHash, mods: &[Mod]) -> String {
+    format!("{}-{:.2}-v{}", hash, mods::rate(mods), VERSION)
+}
+
+/// Difficulty results keyed by difficulty hash and mods. On native, results are also saved to disk.
+pub struct DifficultyCache {
+    #[cfg_attr(target_family = "wasm", allow(dead_code))]
+    cache_path: PathBuf,
+    cache: RefCell<HashMap<String, Arc<DifficultyAttributes>>>,
+}
+
+impl DifficultyCache {
+    pub fn new(cache_path: impl Into<PathBuf>) -> Self {
+        DifficultyCache {
+            cache_path: cache_path.into(),
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Previously calculated result for the difficulty with hash `hash` played with `mods`, if any.
+    pub fn get(&self, hash: &DifficultyHash, mods: &[Mod]) -> Option<Arc<DifficultyAttributes>> {
+        let key = cache_key(hash, mods);
+        if let Some(attributes) = self.cache.borrow().get(&key) {
+            return Some(attributes.clone());
+        }
+
+        let attributes = Arc::new(self.load(&key)?);
+        self.cache.borrow_mut().insert(key, attributes.clone());
+        Some(attributes)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn load(&self, key: &str) -> Option<DifficultyAttributes> {
+        let content = std::fs::read(self.cache_path.join(key)).ok()?;
+        bincode::deserialize(&content).ok()
+    }
+
+    #[cfg(target_family = "wasm")]
+    fn load(&self, _key: &str) -> Option<DifficultyAttributes> {
+        None
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn save(&self, key: &str, attributes: &DifficultyAttributes) {
+        // The result is still kept in memory, so it's only calculated again after a restart.
+        let saved = std::fs::create_dir_all(&self.cache_path).and_then(|()| {
+            std::fs::write(
+                self.cache_path.join(key),
+                bincode::serialize(attributes).unwrap(),
+            )
+        });
+        if let Err(e) = saved {
+            log!(LogType::General, "Couldn't save difficulty {}: {}", key, e);
+        }
+    }
+
+    #[cfg(target_family = "wasm")]
+    fn save(&self, _key: &str, _attributes: &DifficultyAttributes) {}
+
+    /// Cached result for `chart` played with `mods`, calculating it if needed.
+    pub fn get_or_calculate(
+        &self,
+        hash: &DifficultyHash,
+        mods: &[Mod],
+        chart: impl FnOnce() -> Chart,
+    ) -> Arc<DifficultyAttributes> {
+        if let Some(attributes) = self.get(hash, mods) {
+            return attributes;
+        }
+
+        self.insert(hash, mods, calculate(&chart(), mods))
+    }
+
+    /// Adds a result calculated outside the cache, e.g. with [`calculate_async`].
+    pub fn insert(
+        &self,
+        hash: &DifficultyHash,
+        mods: &[Mod],
+        attributes: DifficultyAttributes,
+    ) -> Arc<DifficultyAttributes> {
+        let key = cache_key(hash, mods);
+        self.save(&key, &attributes);
+        let attributes = Arc::new(attributes);
+        self.cache.borrow_mut().insert(key, attributes.clone());
+        attributes
+    }
+}
+
+#[test]
+fn test_calculate() {
+    use crate::chart::{Additions, FruitKind};
+    use macroquad::prelude::WHITE;
+
+    let chart = |spacing: f32, distance: f32| Chart {
+        fruits: (0..100)
+            .map(|idx| Fruit {
+                position: 256. + if idx % 2 == 0 { distance } else { -distance },
+                time: idx as f32 * spacing,
+                hyper: None,
+                kind: FruitKind::Fruit,
+                additions: Additions {
+                    whistle: false,
+                    finish: false,
+                    clap: false,
+                },
+                color: WHITE,
+                plate_reset: false,
+                fall_multiplier: 1.,
+            })
+            .collect(),
+        events: Vec::new(),
+        fall_time: 1.,
+        fruit_radius: 32.,
+        catcher_width: 100.,
+        hp_drain: 5.,
+    };
+
+    let base = calculate(&chart(0.3, 50.), &[]);
+    assert!(base.star_rating > 0.);
+    assert_eq!(base.max_combo, 100);
+    assert_eq!(
+        base.strains.len(),
+        (99. * 0.3 / SECTION_LENGTH) as usize + 1
+    );
+    assert_eq!(base.length, base.strains.len() as f32 * SECTION_LENGTH);
+
+    // Denser and wider patterns are harder, and so is playing faster.
+    assert!(calculate(&chart(0.2, 50.), &[]).star_rating > base.star_rating);
+    assert!(calculate(&chart(0.3, 100.), &[]).star_rating > base.star_rating);
+    assert!(calculate(&chart(0.3, 50.), &[Mod::Rate(1.5)]).star_rating > base.star_rating);
+    // Mods that don't change the timing don't change the difficulty.
+    assert_eq!(
+        calculate(&chart(0.3, 50.), &[Mod::Hidden]).star_rating,
+        base.star_rating
+    );
+}
+
+#[test]
+fn test_chart_hash() {
+    assert_eq!(chart_hash(b""), 0xcbf29ce484222325);
+    assert_eq!(chart_hash(b"a"), 0xaf63dc4c8601ec8c);
+    assert_ne!(chart_hash(b"ab"), chart_hash(b"ba"));
+}
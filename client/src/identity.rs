@@ -0,0 +1,127 @@
+//! Content based identity of difficulties, so every install agrees on which difficulty a score is for.
+
+use crate::{
+    chart::{Chart, FruitKind},
+    convert::ConvertFrom,
+    difficulty,
+};
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+
+/// Hash of a converted chart, followed by the MD5 of the `.osu` file it was converted from when there is one.
+/// Empty for difficulties that haven't been hashed yet.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DifficultyHash(String);
+
+impl DifficultyHash {
+    pub fn new(chart: &Chart, source: Option<&[u8]>) -> Self {
+        let mut hash = format!("{:016x}", difficulty::chart_hash(&chart_content(chart)));
+        if let Some(source) = source {
+            hash.push('-');
+            hash.push_str(&to_hex(&Md5::digest(source)));
+        }
+        DifficultyHash(hash)
+    }
+
+    /// Hash of the chart converted from the `.osu` file `content`, `None` if it can't be parsed.
+    pub fn from_osu(content: &[u8]) -> Option<Self> {
+        let beatmap = osu_parser::load_content(
+            std::str::from_utf8(content).ok()?,
+            osu_parser::BeatmapParseOptions::default(),
+        )
+        .ok()?;
+        Some(DifficultyHash::new(
+            &Chart::convert_from(&beatmap),
+            Some(content),
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the difficulty hasn't been hashed yet.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for DifficultyHash {
+    fn from(hash: String) -> Self {
+        DifficultyHash(hash)
+    }
+}
+
+impl std::fmt::Display for DifficultyHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Everything about a chart that affects gameplay, as bytes.
+fn chart_content(chart: &Chart) -> Vec<u8> {
+    let mut content = Vec::with_capacity(16 + chart.fruits.len() * 22);
+    for value in [
+        chart.fall_time,
+        chart.fruit_radius,
+        chart.catcher_width,
+        chart.hp_drain,
+    ] {
+        content.extend(value.to_le_bytes());
+    }
+    for fruit in &chart.fruits {
+        content.extend(fruit.time.to_le_bytes());
+        content.extend(fruit.position.to_le_bytes());
+        content.extend(fruit.hyper.unwrap_or(0.).to_le_bytes());
+        content.extend(fruit.fall_multiplier.to_le_bytes());
+        content.push(match fruit.kind {
+            FruitKind::Fruit => 0,
+            FruitKind::Droplet => 1,
+            FruitKind::TinyDroplet => 2,
+            FruitKind::Banana => 3,
+        });
+        content.push(fruit.plate_reset as u8);
+    }
+    content
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn test_difficulty_hash() {
+    use crate::chart::{Additions, Fruit};
+    use macroquad::prelude::WHITE;
+
+    let fruit = |position: f32| Fruit {
+        position,
+        time: 1.,
+        hyper: None,
+        kind: FruitKind::Fruit,
+        additions: Additions {
+            whistle: false,
+            finish: false,
+            clap: false,
+        },
+        color: WHITE,
+        plate_reset: false,
+        fall_multiplier: 1.,
+    };
+    let chart = |position: f32| Chart {
+        fruits: vec![fruit(position)],
+        events: Vec::new(),
+        fall_time: 1.,
+        fruit_radius: 64.,
+        catcher_width: 100.,
+        hp_drain: 5.,
+    };
+
+    let hash = DifficultyHash::new(&chart(100.), None);
+    assert_eq!(hash, DifficultyHash::new(&chart(100.), None));
+    assert_eq!(hash.as_str().len(), 16);
+    assert_ne!(hash, DifficultyHash::new(&chart(200.), None));
+
+    let hash = DifficultyHash::new(&chart(100.), Some(b""));
+    assert!(hash.as_str().ends_with("-d41d8cd98f00b204e9800998ecf8427e"));
+}
@@ -0,0 +1,159 @@
+//! Importing osu! beatmap sets into the chart library. Sets are converted by [`beatmap_import`],
+//! this adds them to the library of the running game.
+
+use crate::{library, LogType};
+use aether::log;
+pub use beatmap_import::*;
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
+#[cfg(target_family = "wasm")]
+use {
+    crate::repository,
+    std::{cell::RefCell, rc::Rc},
+    wasm_bindgen::{prelude::*, JsCast},
+};
+
+/// Logs what was imported from a beatmap set.
+pub fn log_imported(folder: &ChartFolder) {
+    log!(
+        LogType::General,
+        "Imported {}, skipped {} difficulties",
+        folder.title,
+        folder.skipped.len()
+    );
+    if let Some(e) = &folder.background_error {
+        log!(
+            LogType::General,
+            "Left out the background of {}: {}",
+            folder.title,
+            e
+        );
+    }
+}
+
+/// Stores `folder` in browser storage as a chart of the library folder. A chart of the same set gets
+/// the new difficulties added to it, other sets with the same title get a numbered folder. Files that
+/// are already there are never overwritten, and are left out of `folder.files`.
+#[cfg(target_family = "wasm")]
+pub async fn install(folder: &mut ChartFolder) -> Result<()> {
+    let mut idx = 1;
+    loop {
+        let title = numbered(&folder.title, idx);
+        idx += 1;
+        let manifest_path = library::chart_file(&title, library::MANIFEST);
+        // Charts of the library folder on the server can't be added to, only imported ones.
+        let listed = library::load_file(&manifest_path).await.is_ok();
+        let manifest = match repository::load_file_local(&manifest_path)
+            .await
+            .map_err(storage_error)?
+        {
+            Some(content) => serde_json::from_slice::<Vec<String>>(&content).unwrap_or_default(),
+            None if listed => continue,
+            None => Vec::new(),
+        };
+        let existing = match first_difficulty(&manifest) {
+            Some(name) => repository::load_file_local(&library::chart_file(&title, name))
+                .await
+                .map_err(storage_error)?,
+            None => None,
+        };
+        if !can_merge(folder, &manifest, existing.as_deref()) {
+            continue;
+        }
+
+        folder.title = title;
+        folder.files.retain(|file| !manifest.contains(&file.name));
+        let mut manifest = manifest;
+        for file in &folder.files {
+            repository::store_file_local(
+                &library::chart_file(&folder.title, &file.name),
+                &file.content,
+            )
+            .await
+            .map_err(storage_error)?;
+            manifest.push(file.name.clone());
+        }
+        repository::store_file_local(&manifest_path, &serde_json::to_vec(&manifest).unwrap())
+            .await
+            .map_err(storage_error)?;
+        library::add_imported_title(&folder.title).map_err(storage_error)?;
+        return Ok(());
+    }
+}
+
+#[cfg(target_family = "wasm")]
+fn storage_error(e: repository::Error) -> Error {
+    Error::Storage(e.to_string())
+}
+
+/// Opens the browser's file picker, for `.osz` files or with `folder` for a folder of beatmap files.
+/// Everything picked arrives on the returned channel at once, after it's been read.
+#[cfg(target_family = "wasm")]
+pub fn pick_files(folder: bool) -> flume::Receiver<Vec<File>> {
+    let (tx, rx) = flume::bounded(1);
+    let document = web_sys::window().unwrap().document().unwrap();
+    let input = document
+        .create_element("input")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlInputElement>()
+        .unwrap();
+    input.set_type("file");
+    input.set_multiple(true);
+    if folder {
+        input.set_webkitdirectory(true);
+    } else {
+        input.set_accept(".osz");
+    }
+
+    let input_c = input.clone();
+    let onchange_cb = Closure::wrap(Box::new(move |_e: web_sys::Event| {
+        let list = match input_c.files() {
+            Some(list) => list,
+            None => return,
+        };
+        let count = list.length() as usize;
+        let read = Rc::new(RefCell::new(Vec::with_capacity(count)));
+        for file in (0..list.length()).filter_map(|idx| list.get(idx)) {
+            // Files in a folder are named by their path from the picked folder, which isn't part of the set.
+            let path = file.webkit_relative_path();
+            let name = match path.split_once('/') {
+                Some((_, name)) => name.to_owned(),
+                None => file.name(),
+            };
+
+            let fr = web_sys::FileReader::new().unwrap();
+            let fr_c = fr.clone();
+            let read = read.clone();
+            let tx = tx.clone();
+            let onloadend_cb = Closure::wrap(Box::new(move |_e: web_sys::ProgressEvent| {
+                let content = js_sys::Uint8Array::new(&fr_c.result().unwrap()).to_vec();
+                let mut read = read.borrow_mut();
+                read.push(File {
+                    name: name.clone(),
+                    content,
+                });
+                if read.len() == count {
+                    let _ = tx.send(std::mem::take(&mut *read));
+                }
+            })
+                as Box<dyn FnMut(web_sys::ProgressEvent)>);
+            fr.set_onloadend(Some(onloadend_cb.as_ref().unchecked_ref()));
+            fr.read_as_array_buffer(&file).expect("File not readable");
+            onloadend_cb.forget();
+        }
+    }) as Box<dyn FnMut(web_sys::Event)>);
+    input.set_onchange(Some(onchange_cb.as_ref().unchecked_ref()));
+    onchange_cb.forget();
+
+    input.click();
+    rx
+}
+
+/// Converts the `.osz` at `path` and writes it to the library folder.
+/// The library has to be rescanned for the chart to show up.
+#[cfg(not(target_family = "wasm"))]
+pub fn import_osz(path: &Path) -> Result<ChartFolder> {
+    let mut folder = convert_set(&unpack_osz(&std::fs::read(path)?)?)?;
+    install(&mut folder, Path::new(&library::library_path()))?;
+    Ok(folder)
+}
@@ -0,0 +1,171 @@
+use crate::{
+    config::{Action, KeyBinds},
+    rulesets::catch::CatchInput,
+};
+use gilrs::{Axis, Button, Gilrs};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Analog stick values closer to the center than this are ignored.
+const STICK_DEADZONE: f32 = 0.25;
+
+/// How far the trigger has to be pulled to dash \[0; 1\].
+const TRIGGER_THRESHOLD: f32 = 0.5;
+
+/// Where catcher input comes from during gameplay. Recorded with replays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputMode {
+    #[default]
+    Keyboard,
+    /// D-pad or left stick to move, right trigger to dash.
+    Gamepad,
+    /// The catcher follows the mouse, holding the left button dashes.
+    Mouse,
+    /// Touching the left or right third of the screen moves, touching the middle third dashes.
+    Touch,
+}
+
+impl InputMode {
+    pub const ALL: [InputMode; 4] = [
+        InputMode::Keyboard,
+        InputMode::Gamepad,
+        InputMode::Mouse,
+        InputMode::Touch,
+    ];
+
+    /// Display name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputMode::Keyboard => "Keyboard",
+            InputMode::Gamepad => "Gamepad",
+            InputMode::Mouse => "Mouse",
+            InputMode::Touch => "Touch",
+        }
+    }
+}
+
+/// Input from the bound keys.
+pub fn keyboard(binds: &KeyBinds) -> CatchInput {
+    CatchInput {
+        left: binds.is_down(Action::Left),
+        right: binds.is_down(Action::Right),
+        dash: binds.is_down(Action::Dash),
+        target: None,
+    }
+}
+
+/// Moves the catcher towards `target`, the playfield position under the mouse.
+pub fn mouse(target: f32) -> CatchInput {
+    CatchInput {
+        left: false,
+        right: false,
+        dash: is_mouse_button_down(MouseButton::Left),
+        target: Some(target),
+    }
+}
+
+/// Input from the touch zones, see [`InputMode::Touch`].
+pub fn touch() -> CatchInput {
+    touch_zones(
+        touches()
+            .iter()
+            .filter(|touch| !matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled))
+            .map(|touch| touch.position.x),
+        screen_width(),
+    )
+}
+
+fn touch_zones(xs: impl Iterator<Item = f32>, width: f32) -> CatchInput {
+    let mut input = CatchInput::default();
+    for x in xs {
+        if x < width / 3. {
+            input.left = true;
+        } else if x > width * 2. / 3. {
+            input.right = true;
+        } else {
+            input.dash = true;
+        }
+    }
+    input
+}
+
+/// Outlines the touch zones, see [`InputMode::Touch`].
+pub fn draw_touch_zones() {
+    let color = Color::new(1., 1., 1., 0.1);
+    draw_rectangle_lines(0., 0., screen_width() / 3., screen_height(), 2., color);
+    draw_rectangle_lines(
+        screen_width() * 2. / 3.,
+        0.,
+        screen_width() / 3.,
+        screen_height(),
+        2.,
+        color,
+    );
+}
+
+/// Stick position with values inside the deadzone ignored.
+fn deadzone(value: f32) -> f32 {
+    if value.abs() < STICK_DEADZONE {
+        0.
+    } else {
+        value
+    }
+}
+
+/// Connected gamepads. If they can't be accessed gamepad input is simply never pressed.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Gamepads {
+            gilrs: Gilrs::new().ok(),
+        }
+    }
+
+    /// Input from the first connected gamepad.
+    pub fn read(&mut self) -> CatchInput {
+        let gilrs = match &mut self.gilrs {
+            Some(gilrs) => gilrs,
+            None => return CatchInput::default(),
+        };
+        // Gamepad state is only updated while handling events.
+        while gilrs.next_event().is_some() {}
+
+        let gamepad = match gilrs.gamepads().next() {
+            Some((_, gamepad)) => gamepad,
+            None => return CatchInput::default(),
+        };
+        let stick = deadzone(gamepad.value(Axis::LeftStickX));
+        let trigger = gamepad
+            .button_data(Button::RightTrigger2)
+            .map_or(0., |data| data.value());
+        CatchInput {
+            left: gamepad.is_pressed(Button::DPadLeft) || stick < 0.,
+            right: gamepad.is_pressed(Button::DPadRight) || stick > 0.,
+            dash: trigger >= TRIGGER_THRESHOLD || gamepad.is_pressed(Button::RightTrigger),
+            target: None,
+        }
+    }
+}
+
+#[test]
+fn test_deadzone() {
+    assert_eq!(deadzone(0.1), 0.);
+    assert_eq!(deadzone(-0.2), 0.);
+    assert_eq!(deadzone(0.5), 0.5);
+    assert_eq!(deadzone(-1.), -1.);
+}
+
+#[test]
+fn test_touch_zones() {
+    let input = touch_zones([100.].into_iter(), 900.);
+    assert!(input.left && !input.right && !input.dash);
+
+    let input = touch_zones([450., 800.].into_iter(), 900.);
+    assert!(!input.left && input.right && input.dash);
+
+    let input = touch_zones(std::iter::empty(), 900.);
+    assert!(!input.left && !input.right && !input.dash);
+}
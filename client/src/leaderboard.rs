@@ -1,19 +1,212 @@
-use crate::rulesets::catch::CatchScore;
+use crate::{
+    identity::DifficultyHash,
+    library::{ChartInfo, DifficultyInfo},
+    mods::Mod,
+    practice::{LoopStats, Section},
+    repository::Result,
+    rulesets::{
+        catch::{CatchJudgement, CatchScore},
+        JudgementResult,
+    },
+    score::{self, Grade, ScoringModel},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 #[cfg(not(target_family = "wasm"))]
 use {
-    crate::rulesets::{catch::CatchJudgement, JudgementResult},
-    gluesql::prelude::{Glue, Payload, SledStorage, Value},
-    std::{collections::HashMap, ops::Deref},
+    crate::{
+        migrations,
+        repository::{self, FromRow, Query, Row},
+        rulesets::catch::JUDGEMENT_COLUMNS,
+    },
+    gluesql::prelude::{Glue, SledStorage},
 };
+#[cfg(target_family = "wasm")]
+use {
+    crate::{
+        replays,
+        repository::{load_local, remove_local, store_file_local, store_local},
+        LogType,
+    },
+    aether::log,
+};
+
+/// Local storage key of the scores on web.
+#[cfg(target_family = "wasm")]
+const SCORES_KEY: &str = "leaderboard/scores";
+
+/// Local storage key of the practice loops on web.
+#[cfg(target_family = "wasm")]
+const PRACTICE_KEY: &str = "leaderboard/practice";
 
 /// An entry on the leaderboard.
 #[derive(Debug, Clone)]
 pub struct LeaderboardEntry {
+    /// Scores set before difficulties were hashed don't have this until their difficulty is scanned.
+    pub diff_hash: Option<DifficultyHash>,
+    /// Player that was logged in, if any.
+    pub username: Option<String>,
     pub score: u32,
     pub accuracy: f32,
+    pub top_combo: u32,
+    pub judgements: BTreeMap<JudgementResult<CatchJudgement>, u32>,
+    pub mods: Vec<Mod>,
+    pub model: ScoringModel,
+    pub grade: Grade,
+    pub pp: f32,
+    /// Unix timestamp of when the play was submitted. Scores set before this was stored don't have it.
+    pub played_at: Option<u64>,
+    /// Time in seconds the play took, 0 if unknown.
+    pub length: f32,
+    /// Name of the replay in the replay library, see [`crate::replays`].
+    pub replay: Option<String>,
+}
+
+/// Totals over a set of plays, shown on the history screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayStats {
+    pub plays: usize,
+    /// Total time played, in seconds.
+    pub play_time: f32,
+    pub average_accuracy: f32,
+}
+
+impl PlayStats {
+    pub fn new(entries: &[LeaderboardEntry]) -> Self {
+        PlayStats {
+            plays: entries.len(),
+            play_time: entries.iter().map(|entry| entry.length).sum(),
+            average_accuracy: if entries.is_empty() {
+                0.
+            } else {
+                entries.iter().map(|entry| entry.accuracy).sum::<f32>() / entries.len() as f32
+            },
+        }
+    }
+}
+
+/// Indices of the `count` plays worth the most pp, best first.
+pub fn top_plays(entries: &[LeaderboardEntry], count: usize) -> Vec<usize> {
+    let mut indices = (0..entries.len()).collect::<Vec<_>>();
+    indices.sort_by(|&a, &b| entries[b].pp.total_cmp(&entries[a].pp));
+    indices.truncate(count);
+    indices
+}
+
+/// A score as it's stored. Accuracy and grades of old scores are worked out when it becomes a [`LeaderboardEntry`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredScore {
+    /// Local id scores were stored by before difficulties were hashed, see [`Leaderboard::link_legacy_scores`].
+    diff_id: Option<u32>,
+    diff_hash: Option<DifficultyHash>,
+    username: Option<String>,
+    /// See [`score::judgement_counts`].
+    judgement_counts: Vec<u32>,
+    score: u32,
+    top_combo: u32,
+    mods: Vec<Mod>,
+    model: Option<String>,
+    grade: Option<String>,
+    pp: f32,
+    played_at: Option<u64>,
+    length: f32,
+    passed: Option<bool>,
+    replay: Option<String>,
+}
+
+impl StoredScore {
+    fn new(score: &CatchScore, length: f32, replay: Option<&str>) -> Self {
+        StoredScore {
+            diff_id: None,
+            diff_hash: Some(score.diff_hash.clone()),
+            username: score.username.clone(),
+            judgement_counts: score::judgement_counts(&score.judgements),
+            score: score.score,
+            top_combo: score.top_combo,
+            mods: score.mods.clone(),
+            model: Some(score.model.key().to_owned()),
+            grade: Some(score.grade.key().to_owned()),
+            pp: score.pp,
+            played_at: Some(
+                instant::SystemTime::now()
+                    .duration_since(instant::SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            ),
+            length,
+            passed: Some(score.passed),
+            replay: replay.map(ToOwned::to_owned),
+        }
+    }
+}
+
+impl From<StoredScore> for LeaderboardEntry {
+    fn from(stored: StoredScore) -> Self {
+        let judgements = score::judgements_from_counts(&stored.judgement_counts);
+        LeaderboardEntry {
+            diff_hash: stored.diff_hash,
+            username: stored.username,
+            score: stored.score,
+            accuracy: score::accuracy(&judgements),
+            top_combo: stored.top_combo,
+            grade: stored
+                .grade
+                .and_then(|grade| Grade::from_key(&grade))
+                .unwrap_or_else(|| Grade::from_judgements(&judgements, &stored.mods)),
+            judgements,
+            mods: stored.mods,
+            model: stored
+                .model
+                .and_then(|model| ScoringModel::from_key(&model))
+                .unwrap_or_default(),
+            pp: stored.pp,
+            played_at: stored.played_at,
+            length: stored.length,
+            replay: stored.replay,
+        }
+    }
 }
 
-/// Leaderboard is an object that takes care of score submission and retreival. Currently native only.
+/// Sorted like the local leaderboard query, best score first.
+#[cfg_attr(not(target_family = "wasm"), allow(dead_code))]
+fn local_leaderboard(
+    scores: Vec<StoredScore>,
+    diff_hash: &DifficultyHash,
+) -> Vec<LeaderboardEntry> {
+    let mut scores = scores
+        .into_iter()
+        .filter(|score| score.diff_hash.as_ref() == Some(diff_hash))
+        .collect::<Vec<_>>();
+    scores.sort_by(|a, b| b.score.cmp(&a.score));
+    scores.into_iter().map(Into::into).collect()
+}
+
+/// Difficulties legacy scores can be linked to, ones that have been hashed.
+fn linkable_difficulties(charts: &[ChartInfo]) -> impl Iterator<Item = &DifficultyInfo> {
+    charts
+        .iter()
+        .flat_map(|chart| &chart.difficulties)
+        .filter(|diff| !diff.hash.is_empty())
+}
+
+/// A practice loop as it's stored on web. Native stores the same columns in the `practice` table.
+#[cfg(target_family = "wasm")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredLoop {
+    diff_id: Option<u32>,
+    diff_hash: Option<DifficultyHash>,
+    start_time: f32,
+    end_time: f32,
+    loop_idx: usize,
+    accuracy: f32,
+    miss_count: u32,
+    top_combo: u32,
+    max_combo: u32,
+    failed: bool,
+}
+
+/// Leaderboard is an object that takes care of score submission and retreival.
+/// Stored in `data/.scores` on native and in local storage on web.
 pub struct Leaderboard {
     #[cfg(not(target_family = "wasm"))]
     glue: Glue<gluesql::sled_storage::sled::IVec, SledStorage>,
@@ -21,75 +214,315 @@ pub struct Leaderboard {
 
 #[cfg(not(target_family = "wasm"))]
 impl Leaderboard {
-    pub async fn new() -> Self {
-        let storage = SledStorage::new("data/.scores").unwrap();
+    pub async fn new() -> Result<Self> {
+        let storage = SledStorage::new("data/.scores")?;
         let mut glue = Glue::new(storage);
 
-        glue.execute_async(include_str!("queries/initialize.sql"))
-            .await
-            .unwrap();
-        Leaderboard { glue }
-    }
-
-    pub async fn submit_score(&mut self, score: &CatchScore) {
-        self.glue
-            .execute_async(&format!(
-                include_str!("queries/insert_leaderboard.sql"),
-                score.diff_id,
-                score.judgements[&JudgementResult::Hit(CatchJudgement::Perfect)],
-                score.judgements[&JudgementResult::Miss],
-                score.score,
-                score.top_combo
-            ))
-            .await
-            .unwrap();
-    }
-
-    pub async fn query_local(&mut self, diff_id: u32) -> Vec<LeaderboardEntry> {
-        let leaderboard = self
-            .glue
-            .execute_async(&format!(
-                include_str!("queries/local_leaderboard.sql"),
-                diff_id
-            ))
-            .await
-            .unwrap();
-        let mut entries = Vec::new();
-        match leaderboard {
-            Payload::Select { labels, rows } => {
-                for row in rows {
-                    let map = labels
-                        .iter()
-                        .map(Deref::deref)
-                        .zip(row.iter().map(|col| match col {
-                            Value::I64(v) => *v as u32,
-                            _ => unreachable!(),
-                        }))
-                        .collect::<HashMap<_, _>>();
-                    let entry = LeaderboardEntry {
-                        score: map["score"],
-                        accuracy: map["hit_count"] as f32
-                            / (map["hit_count"] + map["miss_count"]) as f32,
-                    };
-                    entries.push(entry);
-                }
+        migrations::run(&mut glue, &migrations::SCORES).await?;
+        Ok(Leaderboard { glue })
+    }
+
+    /// Saves a passed play. `length` is the time in seconds it took.
+    pub async fn submit_score(
+        &mut self,
+        score: &CatchScore,
+        length: f32,
+        replay: Option<&str>,
+    ) -> Result<()> {
+        let stored = StoredScore::new(score, length, replay);
+        let query = stored
+            .judgement_counts
+            .iter()
+            .fold(
+                Query::new(include_str!("queries/insert_leaderboard.sql")).bind(stored.diff_id),
+                Query::bind,
+            )
+            .bind(stored.score)
+            .bind(stored.top_combo)
+            .bind(serde_json::to_string(&stored.mods).unwrap())
+            .bind(&stored.model)
+            .bind(&stored.grade)
+            .bind(stored.pp)
+            .bind(stored.played_at)
+            .bind(stored.length)
+            .bind(&stored.username)
+            .bind(stored.passed)
+            .bind(&stored.replay)
+            .bind(&stored.diff_hash);
+        repository::execute_async(&mut self.glue, &query).await?;
+        Ok(())
+    }
+
+    /// Saves the loops of a practice session. Kept apart from scores so they never show up on the leaderboard.
+    pub async fn submit_practice(
+        &mut self,
+        diff_hash: &DifficultyHash,
+        section: Section,
+        loops: &[LoopStats],
+    ) -> Result<()> {
+        for (idx, stats) in loops.iter().enumerate() {
+            let query = Query::new(include_str!("queries/insert_practice.sql"))
+                .bind(None::<u32>)
+                .bind(section.start)
+                .bind(section.end)
+                .bind(idx)
+                .bind(stats.accuracy)
+                .bind(stats.misses)
+                .bind(stats.top_combo)
+                .bind(stats.max_combo)
+                .bind(stats.failed)
+                .bind(diff_hash);
+            repository::execute_async(&mut self.glue, &query).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn query_local(
+        &mut self,
+        diff_hash: &DifficultyHash,
+    ) -> Result<Vec<LeaderboardEntry>> {
+        repository::select_async(
+            &mut self.glue,
+            &Query::new(include_str!("queries/local_leaderboard.sql")).bind(diff_hash),
+        )
+        .await
+    }
+
+    /// Gives scores and practice loops stored by local difficulty id the hash of that difficulty.
+    pub async fn link_legacy_scores(&mut self, charts: &[ChartInfo]) -> Result<()> {
+        for diff in linkable_difficulties(charts) {
+            for sql in [
+                include_str!("queries/link_legacy_scores.sql"),
+                include_str!("queries/link_legacy_practice.sql"),
+            ] {
+                let query = Query::new(sql).bind(&diff.hash).bind(diff.id);
+                repository::execute_async(&mut self.glue, &query).await?;
             }
-            _ => unreachable!(),
         }
+        Ok(())
+    }
 
-        entries
+    /// Every local play on every difficulty.
+    pub async fn query_history(&mut self) -> Result<Vec<LeaderboardEntry>> {
+        repository::select_async(
+            &mut self.glue,
+            &Query::new(include_str!("queries/history.sql")),
+        )
+        .await
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl FromRow for StoredScore {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(StoredScore {
+            diff_id: row.get("diff_id")?,
+            diff_hash: row.get("diff_hash")?,
+            username: row.get("username")?,
+            judgement_counts: JUDGEMENT_COLUMNS
+                .iter()
+                .map(|column| row.get(column))
+                .collect::<Result<_>>()?,
+            score: row.get("score")?,
+            top_combo: row.get("top_combo")?,
+            mods: row
+                .get::<Option<String>>("mods")?
+                .and_then(|mods| serde_json::from_str(&mods).ok())
+                .unwrap_or_default(),
+            model: row.get("model")?,
+            grade: row.get("grade")?,
+            pp: row.get::<Option<f32>>("pp")?.unwrap_or(0.),
+            played_at: row.get("played_at")?,
+            length: row.get::<Option<f32>>("length")?.unwrap_or(0.),
+            passed: row.get("passed")?,
+            replay: row.get("replay")?,
+        })
     }
 }
 
 #[cfg(target_family = "wasm")]
 impl Leaderboard {
-    pub async fn new() -> Self {
-        Leaderboard {}
+    pub async fn new() -> Result<Self> {
+        if let Err(e) = migrate_legacy_replays().await {
+            log!(
+                LogType::General,
+                "Couldn't move replays to IndexedDB: {}",
+                e
+            );
+        }
+        Ok(Leaderboard {})
     }
 
-    pub async fn submit_score(&mut self, _score: &CatchScore) {}
+    /// Saves a passed play. `length` is the time in seconds it took.
+    pub async fn submit_score(
+        &mut self,
+        score: &CatchScore,
+        length: f32,
+        replay: Option<&str>,
+    ) -> Result<()> {
+        let mut scores = load_local::<Vec<StoredScore>>(SCORES_KEY)?.unwrap_or_default();
+        scores.push(StoredScore::new(score, length, replay));
+        store_local(SCORES_KEY, &scores)
+    }
+
+    /// Saves the loops of a practice session. Kept apart from scores so they never show up on the leaderboard.
+    pub async fn submit_practice(
+        &mut self,
+        diff_hash: &DifficultyHash,
+        section: Section,
+        loops: &[LoopStats],
+    ) -> Result<()> {
+        let mut stored = load_local::<Vec<StoredLoop>>(PRACTICE_KEY)?.unwrap_or_default();
+        stored.extend(loops.iter().enumerate().map(|(idx, stats)| StoredLoop {
+            diff_id: None,
+            diff_hash: Some(diff_hash.clone()),
+            start_time: section.start,
+            end_time: section.end,
+            loop_idx: idx,
+            accuracy: stats.accuracy,
+            miss_count: stats.misses,
+            top_combo: stats.top_combo,
+            max_combo: stats.max_combo,
+            failed: stats.failed,
+        }));
+        store_local(PRACTICE_KEY, &stored)
+    }
+
+    pub async fn query_local(
+        &mut self,
+        diff_hash: &DifficultyHash,
+    ) -> Result<Vec<LeaderboardEntry>> {
+        Ok(local_leaderboard(
+            load_local(SCORES_KEY)?.unwrap_or_default(),
+            diff_hash,
+        ))
+    }
 
-    pub async fn query_local(&mut self, _diff_id: u32) -> Vec<LeaderboardEntry> {
-        Vec::new()
+    /// Gives scores and practice loops stored by local difficulty id the hash of that difficulty.
+    pub async fn link_legacy_scores(&mut self, charts: &[ChartInfo]) -> Result<()> {
+        let difficulties = linkable_difficulties(charts).collect::<Vec<_>>();
+        let hash_of = |diff_id: Option<u32>| {
+            difficulties
+                .iter()
+                .find(|diff| Some(diff.id) == diff_id)
+                .map(|diff| diff.hash.clone())
+        };
+
+        let mut scores = load_local::<Vec<StoredScore>>(SCORES_KEY)?.unwrap_or_default();
+        for score in scores.iter_mut().filter(|score| score.diff_hash.is_none()) {
+            score.diff_hash = hash_of(score.diff_id);
+        }
+        store_local(SCORES_KEY, &scores)?;
+
+        let mut loops = load_local::<Vec<StoredLoop>>(PRACTICE_KEY)?.unwrap_or_default();
+        for stored in loops.iter_mut().filter(|stored| stored.diff_hash.is_none()) {
+            stored.diff_hash = hash_of(stored.diff_id);
+        }
+        store_local(PRACTICE_KEY, &loops)
     }
+
+    /// Every local play on every difficulty.
+    pub async fn query_history(&mut self) -> Result<Vec<LeaderboardEntry>> {
+        Ok(load_local::<Vec<StoredScore>>(SCORES_KEY)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+}
+
+/// Moves replays that used to be saved in local storage, as hex, to IndexedDB.
+#[cfg(target_family = "wasm")]
+async fn migrate_legacy_replays() -> Result<()> {
+    for name in replays::list() {
+        let key = replays::storage_key(&name);
+        let hex = match load_local::<String>(&key)? {
+            Some(hex) => hex,
+            None => continue,
+        };
+        match from_hex(&hex) {
+            Some(content) => store_file_local(&key, &content).await?,
+            None => log!(LogType::General, "Dropping unreadable replay {}", name),
+        }
+        remove_local(&key)?;
+    }
+    Ok(())
+}
+
+/// Reads replays saved in local storage, which only holds strings.
+#[cfg_attr(not(target_family = "wasm"), allow(dead_code))]
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+#[test]
+fn test_play_stats() {
+    let entry = |accuracy: f32, pp: f32| LeaderboardEntry {
+        diff_hash: None,
+        username: None,
+        score: 0,
+        accuracy,
+        top_combo: 0,
+        judgements: BTreeMap::new(),
+        mods: Vec::new(),
+        model: ScoringModel::default(),
+        grade: Grade::D,
+        pp,
+        played_at: None,
+        length: 60.,
+        replay: None,
+    };
+    let entries = [entry(1., 100.), entry(0.5, 300.), entry(0.75, 200.)];
+
+    assert_eq!(
+        PlayStats::new(&entries),
+        PlayStats {
+            plays: 3,
+            play_time: 180.,
+            average_accuracy: 0.75,
+        }
+    );
+    assert_eq!(PlayStats::new(&[]).average_accuracy, 0.);
+    assert_eq!(top_plays(&entries, 2), vec![1, 2]);
+    assert_eq!(top_plays(&entries, 5), vec![1, 2, 0]);
+}
+
+#[test]
+fn test_local_leaderboard() {
+    let stored = |diff_hash: &str, score: u32| StoredScore {
+        diff_id: None,
+        diff_hash: Some(DifficultyHash::from(diff_hash.to_owned())),
+        username: None,
+        judgement_counts: vec![10, 0, 0, 0, 0, 0, 0],
+        score,
+        top_combo: 10,
+        mods: Vec::new(),
+        model: None,
+        grade: None,
+        pp: 0.,
+        played_at: None,
+        length: 0.,
+        passed: Some(true),
+        replay: None,
+    };
+    let entries = local_leaderboard(
+        vec![stored("a", 100), stored("b", 300), stored("a", 200)],
+        &DifficultyHash::from("a".to_owned()),
+    );
+
+    assert_eq!(
+        entries.iter().map(|entry| entry.score).collect::<Vec<_>>(),
+        vec![200, 100]
+    );
+    assert_eq!(entries[0].accuracy, 1.);
+    assert_eq!(entries[0].grade, Grade::SS);
+}
+
+#[test]
+fn test_from_hex() {
+    assert_eq!(from_hex("00017fff").unwrap(), [0, 1, 127, 255]);
+    assert_eq!(from_hex("0"), None);
 }
@@ -11,24 +11,39 @@ use std::{
     task::{Context, Poll},
 };
 
+#[cfg(not(target_family = "wasm"))]
 use gluesql::{prelude::Glue, sled_storage::SledStorage};
 
+pub mod analytics;
 pub mod azusa;
 pub mod cache;
 pub mod chart;
 pub mod chat;
 pub mod config;
 pub mod convert;
+pub mod difficulty;
 pub mod frozen;
+pub mod identity;
+pub mod import;
+pub mod input;
 pub mod leaderboard;
+pub mod library;
 pub mod math;
+#[cfg(not(target_family = "wasm"))]
+pub mod migrations;
+pub mod mods;
+pub mod performance;
+pub mod practice;
 pub mod promise;
+pub mod replays;
+pub mod repository;
 pub mod rulesets;
 pub mod score;
 pub mod screen;
 pub mod ui;
 pub mod web_socket;
 
+#[cfg(not(target_family = "wasm"))]
 pub type Database = Glue<gluesql::sled_storage::sled::IVec, SledStorage>;
 
 #[derive(Debug, Hash, PartialEq, Eq)]
@@ -0,0 +1,595 @@
+#[cfg(target_family = "wasm")]
+use crate::repository::{load_file_local, load_local, store_local};
+use crate::{
+    config,
+    identity::DifficultyHash,
+    repository::{Error, Result},
+    LogType,
+};
+use aether::log;
+pub use beatmap_import::{audio_file, sanitize, AUDIO_FORMATS, INDEX, MANIFEST};
+use macroquad::prelude::FileError;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(not(target_family = "wasm"))]
+use {
+    crate::{
+        migrations,
+        repository::{self, ChartRow, DifficultyRow, NextIdsRow, Query},
+        Database,
+    },
+    gluesql::{prelude::Glue, sled_storage::SledStorage},
+};
+
+/// Local storage key of the chart library on web.
+#[cfg(target_family = "wasm")]
+const CHARTS_KEY: &str = "library/charts";
+
+/// Local storage key of the [`NextIds`] on web.
+#[cfg(target_family = "wasm")]
+const NEXT_IDS_KEY: &str = "library/next_ids";
+
+/// Local storage key of the titles of charts imported on web, whose files are in browser storage.
+#[cfg(target_family = "wasm")]
+const IMPORTED_KEY: &str = "library/imported";
+
+/// Where charts are looked for if no other path is configured.
+pub const DEFAULT_LIBRARY_PATH: &str = "resources";
+
+/// Folder the chart folders are in, see [`DEFAULT_LIBRARY_PATH`].
+pub fn library_path() -> String {
+    config::get_value("library_path").unwrap_or_else(|| DEFAULT_LIBRARY_PATH.to_owned())
+}
+
+/// Path of `file` in the folder of the chart called `title`.
+pub fn chart_file(title: &str, file: &str) -> String {
+    format!("{}/{}/{}", library_path(), title, file)
+}
+
+/// Reads the [`MANIFEST`] of the chart called `title`. Returns its files and the one that's its music.
+pub async fn read_manifest(title: &str) -> Result<(Vec<String>, String)> {
+    let files = read_json::<Vec<String>>(&chart_file(title, MANIFEST)).await?;
+    let audio = audio_file(&files)
+        .ok_or_else(|| Error::MissingAudio(title.to_owned()))?
+        .to_owned();
+    Ok((files, audio))
+}
+
+/// Reads a file of the library. On web, files of imported charts are in browser storage, keyed by their path.
+pub async fn load_file(path: &str) -> std::result::Result<Vec<u8>, FileError> {
+    #[cfg(target_family = "wasm")]
+    if let Ok(Some(content)) = load_file_local(path).await {
+        return Ok(content);
+    }
+    macroquad::prelude::load_file(path).await
+}
+
+/// Titles of the charts imported on web.
+#[cfg(target_family = "wasm")]
+pub fn imported_titles() -> Result<Vec<String>> {
+    Ok(load_local(IMPORTED_KEY)?.unwrap_or_default())
+}
+
+#[cfg(target_family = "wasm")]
+pub fn add_imported_title(title: &str) -> Result<()> {
+    let mut titles = imported_titles()?;
+    if !titles.iter().any(|other| other == title) {
+        titles.push(title.to_owned());
+        store_local(IMPORTED_KEY, &titles)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyInfo {
+    /// Only meaningful to this install, scores and leaderboards use `hash`.
+    pub id: u32,
+    /// Empty until the difficulty has been scanned.
+    #[serde(default)]
+    pub hash: DifficultyHash,
+    pub name: String,
+    /// The `.osu` file when it was hashed, `None` where files have no modification time.
+    #[serde(default)]
+    pub stamp: Option<FileStamp>,
+}
+
+/// Size and modification time of a file, which tell whether it changed since it was last read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    /// Milliseconds since the Unix epoch.
+    pub modified: u64,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl FileStamp {
+    fn new(metadata: &std::fs::Metadata) -> Option<Self> {
+        Some(FileStamp {
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()?
+                .as_millis() as u64,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartInfo {
+    pub id: u32,
+    pub title: String,
+    pub difficulties: Vec<DifficultyInfo>,
+}
+
+/// A chart folder found by [`scan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannedChart {
+    /// Name of the folder.
+    pub title: String,
+    pub difficulties: Vec<ScannedDifficulty>,
+}
+
+/// A `.osu` file found by [`scan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannedDifficulty {
+    /// Name of the file, without extension.
+    pub name: String,
+    pub hash: DifficultyHash,
+    pub stamp: Option<FileStamp>,
+}
+
+impl ScannedDifficulty {
+    /// `None` if `content` isn't a chart that can be played.
+    fn new(title: &str, name: &str, content: &[u8], stamp: Option<FileStamp>) -> Option<Self> {
+        match DifficultyHash::from_osu(content) {
+            Some(hash) => Some(ScannedDifficulty {
+                name: name.to_owned(),
+                hash,
+                stamp,
+            }),
+            None => {
+                log!(LogType::General, "Couldn't parse {} [{}]", title, name);
+                None
+            }
+        }
+    }
+}
+
+/// Finds the chart folders in `path`, sorted by title. Difficulties of `library` whose files haven't
+/// changed since they were hashed keep their hash, without reading them again.
+#[cfg(not(target_family = "wasm"))]
+pub async fn scan(path: &str, library: &[ChartInfo]) -> Result<Vec<ScannedChart>> {
+    let scan_error = |e: std::io::Error| Error::Scan(format!("{}: {}", path, e));
+
+    let mut charts = Vec::new();
+    for entry in std::fs::read_dir(path).map_err(scan_error)? {
+        let folder = entry.map_err(scan_error)?.path();
+        let manifest = match std::fs::read(folder.join(MANIFEST)) {
+            Ok(manifest) => manifest,
+            Err(_) => continue,
+        };
+        let title = match folder.file_name().and_then(|name| name.to_str()) {
+            Some(title) => title.to_owned(),
+            None => continue,
+        };
+        let files = serde_json::from_slice::<Vec<String>>(&manifest).unwrap_or_default();
+        if !audio_file(&files).map_or(false, |audio| folder.join(audio).is_file()) {
+            log!(
+                LogType::General,
+                "Skipping {}, it has no music that can be played",
+                title
+            );
+            continue;
+        }
+        let known = library
+            .iter()
+            .find(|chart| chart.title == title)
+            .map_or(&[][..], |chart| chart.difficulties.as_slice());
+        let mut difficulties = std::fs::read_dir(&folder)
+            .map_err(scan_error)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "osu" {
+                    return None;
+                }
+                let name = path.file_stem()?.to_str()?;
+                let stamp = std::fs::metadata(&path)
+                    .ok()
+                    .and_then(|metadata| FileStamp::new(&metadata));
+                let unchanged = known.iter().find(|diff| {
+                    diff.name == name
+                        && stamp.is_some()
+                        && diff.stamp == stamp
+                        && !diff.hash.is_empty()
+                });
+                match unchanged {
+                    Some(diff) => Some(ScannedDifficulty {
+                        name: name.to_owned(),
+                        hash: diff.hash.clone(),
+                        stamp,
+                    }),
+                    None => {
+                        ScannedDifficulty::new(&title, name, &std::fs::read(&path).ok()?, stamp)
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        difficulties.sort_by(|a, b| a.name.cmp(&b.name));
+        if !difficulties.is_empty() {
+            charts.push(ScannedChart {
+                title,
+                difficulties,
+            });
+        }
+    }
+    charts.sort_by(|a, b| a.title.cmp(&b.title));
+    Ok(charts)
+}
+
+/// Finds the chart folders listed in the [`INDEX`] of `path` and the imported ones, sorted by title.
+/// Difficulties are the `.osu` files listed in each manifest. Files have no modification time here,
+/// so every one is read.
+#[cfg(target_family = "wasm")]
+pub async fn scan(path: &str, _library: &[ChartInfo]) -> Result<Vec<ScannedChart>> {
+    let mut titles = imported_titles()?;
+    match read_json::<Vec<String>>(&format!("{}/{}", path, INDEX)).await {
+        Ok(listed) => titles.extend(listed),
+        // Imported charts can be played without a server that has any.
+        Err(e) if titles.is_empty() => return Err(e),
+        Err(_) => {}
+    }
+    titles.sort();
+    titles.dedup();
+
+    let mut charts = Vec::new();
+    for title in titles {
+        // A listed folder could have been removed since.
+        let files =
+            match read_json::<Vec<String>>(&format!("{}/{}/{}", path, title, MANIFEST)).await {
+                Ok(files) => files,
+                Err(_) => continue,
+            };
+        if audio_file(&files).is_none() {
+            log!(
+                LogType::General,
+                "Skipping {}, it has no music that can be played",
+                title
+            );
+            continue;
+        }
+        let mut difficulties = Vec::new();
+        for name in files.iter().filter_map(|file| file.strip_suffix(".osu")) {
+            let content = match load_file(&format!("{}/{}/{}.osu", path, title, name)).await {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+            difficulties.extend(ScannedDifficulty::new(&title, name, &content, None));
+        }
+        difficulties.sort_by(|a, b| a.name.cmp(&b.name));
+        if !difficulties.is_empty() {
+            charts.push(ScannedChart {
+                title,
+                difficulties,
+            });
+        }
+    }
+    charts.sort_by(|a, b| a.title.cmp(&b.title));
+    Ok(charts)
+}
+
+async fn read_json<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let content = load_file(path)
+        .await
+        .map_err(|e| Error::Scan(format!("{}: {:?}", path, e)))?;
+    serde_json::from_slice(&content).map_err(|e| Error::Scan(format!("{}: {}", path, e)))
+}
+
+/// Ids the next new chart and difficulty get. They only ever grow, so ids of removed charts
+/// aren't given to other charts, which could then be mistaken for them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NextIds {
+    pub chart: u32,
+    pub difficulty: u32,
+}
+
+impl Default for NextIds {
+    fn default() -> Self {
+        NextIds {
+            chart: 1,
+            difficulty: 1,
+        }
+    }
+}
+
+/// The library after a scan. Charts and difficulties that were already in `library` keep their ids,
+/// new ones get ids from `next_ids`, and charts that weren't found are left out.
+/// Hashes always come from the scan, since a file can change without being renamed.
+fn merge(
+    library: &[ChartInfo],
+    scanned: &[ScannedChart],
+    next_ids: &mut NextIds,
+) -> Vec<ChartInfo> {
+    // Libraries from before the ids were stored only know the ids they still have.
+    let mut next_chart_id = library
+        .iter()
+        .map(|chart| chart.id + 1)
+        .fold(next_ids.chart, u32::max);
+    let mut next_diff_id = library
+        .iter()
+        .flat_map(|chart| &chart.difficulties)
+        .map(|diff| diff.id + 1)
+        .fold(next_ids.difficulty, u32::max);
+
+    let merged = scanned
+        .iter()
+        .map(|scanned| {
+            let existing = library.iter().find(|chart| chart.title == scanned.title);
+            let id = existing.map(|chart| chart.id).unwrap_or_else(|| {
+                next_chart_id += 1;
+                next_chart_id - 1
+            });
+            let difficulties = scanned
+                .difficulties
+                .iter()
+                .map(|scanned| DifficultyInfo {
+                    id: existing
+                        .and_then(|chart| {
+                            chart
+                                .difficulties
+                                .iter()
+                                .find(|diff| diff.name == scanned.name)
+                        })
+                        .map(|diff| diff.id)
+                        .unwrap_or_else(|| {
+                            next_diff_id += 1;
+                            next_diff_id - 1
+                        }),
+                    hash: scanned.hash.clone(),
+                    name: scanned.name.clone(),
+                    stamp: scanned.stamp,
+                })
+                .collect();
+            ChartInfo {
+                id,
+                title: scanned.title.clone(),
+                difficulties,
+            }
+        })
+        .collect();
+    *next_ids = NextIds {
+        chart: next_chart_id,
+        difficulty: next_diff_id,
+    };
+    merged
+}
+
+/// The charts that can be played. Stored in `data/.chart` on native and in local storage on web.
+pub struct ChartLibrary {
+    #[cfg(not(target_family = "wasm"))]
+    glue: Database,
+}
+
+impl ChartLibrary {
+    /// Scans the library folder and updates the library to match it.
+    pub async fn rescan(&mut self) -> Result<()> {
+        let library = self.charts()?;
+        let scanned = scan(&library_path(), &library).await?;
+        self.update(&scanned)
+    }
+
+    /// Only writes the charts that were added, removed or changed.
+    pub fn update(&mut self, scanned: &[ScannedChart]) -> Result<()> {
+        let library = self.charts()?;
+        let mut next_ids = self.next_ids()?;
+        let merged = merge(&library, scanned, &mut next_ids);
+        self.set_next_ids(next_ids)?;
+        for chart in &library {
+            if !merged.iter().any(|merged| merged.id == chart.id) {
+                self.remove_chart(chart.id)?;
+            }
+        }
+        for chart in &merged {
+            if !library.contains(chart) {
+                self.add_chart(chart)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl ChartLibrary {
+    pub async fn new() -> Result<Self> {
+        let mut glue = Glue::new(SledStorage::new("data/.chart")?);
+        migrations::run(&mut glue, &migrations::CHARTS).await?;
+        Ok(ChartLibrary { glue })
+    }
+
+    /// Sorted by title, difficulties sorted by name.
+    pub fn charts(&mut self) -> Result<Vec<ChartInfo>> {
+        repository::charts(&mut self.glue)?
+            .into_iter()
+            .map(|chart| {
+                Ok(ChartInfo {
+                    difficulties: repository::difficulties(&mut self.glue, chart.id)?
+                        .into_iter()
+                        .map(|diff| DifficultyInfo {
+                            id: diff.id,
+                            hash: diff.hash.unwrap_or_default(),
+                            name: diff.title,
+                            stamp: diff
+                                .size
+                                .zip(diff.modified)
+                                .map(|(size, modified)| FileStamp { size, modified }),
+                        })
+                        .collect(),
+                    id: chart.id,
+                    title: chart.title,
+                })
+            })
+            .collect()
+    }
+
+    /// Adds a chart with its difficulties, replacing the chart with the same id if there is one.
+    pub fn add_chart(&mut self, chart: &ChartInfo) -> Result<()> {
+        self.remove_chart(chart.id)?;
+        repository::insert_chart(
+            &mut self.glue,
+            &ChartRow {
+                id: chart.id,
+                title: chart.title.clone(),
+            },
+        )?;
+        for diff in &chart.difficulties {
+            repository::insert_difficulty(
+                &mut self.glue,
+                &DifficultyRow {
+                    chart_id: chart.id,
+                    id: diff.id,
+                    title: diff.name.clone(),
+                    hash: Some(diff.hash.clone()).filter(|hash| !hash.is_empty()),
+                    size: diff.stamp.map(|stamp| stamp.size),
+                    modified: diff.stamp.map(|stamp| stamp.modified),
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    fn next_ids(&mut self) -> Result<NextIds> {
+        Ok(repository::next_ids(&mut self.glue)?
+            .map(|row| NextIds {
+                chart: row.chart_id,
+                difficulty: row.diff_id,
+            })
+            .unwrap_or_default())
+    }
+
+    fn set_next_ids(&mut self, next_ids: NextIds) -> Result<()> {
+        repository::update_next_ids(
+            &mut self.glue,
+            &NextIdsRow {
+                chart_id: next_ids.chart,
+                diff_id: next_ids.difficulty,
+            },
+        )
+    }
+
+    pub fn remove_chart(&mut self, id: u32) -> Result<()> {
+        repository::execute(
+            &mut self.glue,
+            &Query::new(include_str!("queries/delete_difficulties.sql")).bind(id),
+        )?;
+        repository::execute(
+            &mut self.glue,
+            &Query::new(include_str!("queries/delete_chart.sql")).bind(id),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(target_family = "wasm")]
+impl ChartLibrary {
+    pub async fn new() -> Result<Self> {
+        Ok(ChartLibrary {})
+    }
+
+    /// Sorted by title, difficulties sorted by name.
+    pub fn charts(&mut self) -> Result<Vec<ChartInfo>> {
+        Ok(load_local(CHARTS_KEY)?.unwrap_or_default())
+    }
+
+    /// Adds a chart with its difficulties, replacing the chart with the same id if there is one.
+    pub fn add_chart(&mut self, chart: &ChartInfo) -> Result<()> {
+        let mut charts = self.charts()?;
+        charts.retain(|other| other.id != chart.id);
+        let mut chart = chart.clone();
+        chart.difficulties.sort_by(|a, b| a.name.cmp(&b.name));
+        charts.push(chart);
+        charts.sort_by(|a, b| a.title.cmp(&b.title));
+        store_local(CHARTS_KEY, &charts)
+    }
+
+    fn next_ids(&mut self) -> Result<NextIds> {
+        Ok(load_local(NEXT_IDS_KEY)?.unwrap_or_default())
+    }
+
+    fn set_next_ids(&mut self, next_ids: NextIds) -> Result<()> {
+        store_local(NEXT_IDS_KEY, &next_ids)
+    }
+
+    pub fn remove_chart(&mut self, id: u32) -> Result<()> {
+        let mut charts = self.charts()?;
+        charts.retain(|chart| chart.id != id);
+        store_local(CHARTS_KEY, &charts)
+    }
+}
+
+#[test]
+fn test_merge() {
+    let library = [ChartInfo {
+        id: 1,
+        title: "Kizuato".to_owned(),
+        difficulties: vec![
+            DifficultyInfo {
+                id: 1,
+                hash: DifficultyHash::default(),
+                name: "Platter".to_owned(),
+                stamp: None,
+            },
+            DifficultyInfo {
+                id: 2,
+                hash: DifficultyHash::default(),
+                name: "Ascendance's Rain".to_owned(),
+                stamp: None,
+            },
+        ],
+    }];
+    let scanned_diff = |name: &str| ScannedDifficulty {
+        name: name.to_owned(),
+        hash: DifficultyHash::from(format!("hash of {}", name)),
+        stamp: None,
+    };
+    let scanned = [
+        ScannedChart {
+            title: "Another".to_owned(),
+            difficulties: vec![scanned_diff("Normal")],
+        },
+        ScannedChart {
+            title: "Kizuato".to_owned(),
+            difficulties: vec![scanned_diff("Insane"), scanned_diff("Platter")],
+        },
+    ];
+
+    let ids = |chart: &ChartInfo| {
+        chart
+            .difficulties
+            .iter()
+            .map(|diff| (diff.name.as_str(), diff.id))
+            .collect::<Vec<_>>()
+    };
+    let mut next_ids = NextIds::default();
+    let merged = merge(&library, &scanned, &mut next_ids);
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[0].id, 2);
+    assert_eq!(ids(&merged[0]), vec![("Normal", 3)]);
+    assert_eq!(merged[1].id, 1);
+    assert_eq!(ids(&merged[1]), vec![("Insane", 4), ("Platter", 1)]);
+    assert_eq!(merged[1].difficulties[1].hash.as_str(), "hash of Platter");
+    assert_eq!(
+        next_ids,
+        NextIds {
+            chart: 3,
+            difficulty: 5
+        }
+    );
+
+    assert_eq!(merge(&merged, &scanned, &mut next_ids), merged);
+
+    // Ids of removed charts aren't given out again.
+    assert!(merge(&merged, &[], &mut next_ids).is_empty());
+    let readded = merge(&[], &scanned[..1], &mut next_ids);
+    assert_eq!(readded[0].id, 3);
+    assert_eq!(ids(&readded[0]), vec![("Normal", 5)]);
+}
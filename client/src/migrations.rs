@@ -0,0 +1,286 @@
+use crate::{
+    repository::{self, Query, Result},
+    Database,
+};
+use gluesql::{
+    executor::FetchError,
+    prelude::{Payload, Value},
+};
+
+/// A change to a database schema. Each migration is applied once, in order of `version`.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    /// Statements separated by `;`.
+    pub sql: &'static str,
+}
+
+/// Every migration of a database.
+pub struct Schema {
+    pub migrations: &'static [Migration],
+    /// Table created by the first migration. Databases created before migrations were tracked
+    /// already have it, so the first migration counts as applied for them.
+    pub baseline_table: &'static str,
+}
+
+/// Local scores, `data/.scores`.
+pub const SCORES: Schema = Schema {
+    migrations: &[
+        Migration {
+            version: 1,
+            name: "initial",
+            sql: include_str!("queries/migrations/scores/001_initial.sql"),
+        },
+        Migration {
+            version: 2,
+            name: "practice",
+            sql: include_str!("queries/migrations/scores/002_practice.sql"),
+        },
+        Migration {
+            version: 3,
+            name: "full_score",
+            sql: include_str!("queries/migrations/scores/003_full_score.sql"),
+        },
+        Migration {
+            version: 4,
+            name: "difficulty_hash",
+            sql: include_str!("queries/migrations/scores/004_difficulty_hash.sql"),
+        },
+        Migration {
+            version: 5,
+            name: "mods",
+            sql: include_str!("queries/migrations/scores/005_mods.sql"),
+        },
+        Migration {
+            version: 6,
+            name: "judgements",
+            sql: include_str!("queries/migrations/scores/006_judgements.sql"),
+        },
+        Migration {
+            version: 7,
+            name: "model",
+            sql: include_str!("queries/migrations/scores/007_model.sql"),
+        },
+        Migration {
+            version: 8,
+            name: "grade",
+            sql: include_str!("queries/migrations/scores/008_grade.sql"),
+        },
+        Migration {
+            version: 9,
+            name: "pp",
+            sql: include_str!("queries/migrations/scores/009_pp.sql"),
+        },
+        Migration {
+            version: 10,
+            name: "history",
+            sql: include_str!("queries/migrations/scores/010_history.sql"),
+        },
+    ],
+    baseline_table: "scores",
+};
+
+/// Chart library, `data/.chart`.
+pub const CHARTS: Schema = Schema {
+    migrations: &[
+        Migration {
+            version: 1,
+            name: "initial",
+            sql: include_str!("queries/migrations/charts/001_initial.sql"),
+        },
+        Migration {
+            version: 2,
+            name: "difficulty_hash",
+            sql: include_str!("queries/migrations/charts/002_difficulty_hash.sql"),
+        },
+        Migration {
+            version: 3,
+            name: "next_ids",
+            sql: include_str!("queries/migrations/charts/003_next_ids.sql"),
+        },
+        Migration {
+            version: 4,
+            name: "file_stamp",
+            sql: include_str!("queries/migrations/charts/004_file_stamp.sql"),
+        },
+    ],
+    baseline_table: "charts",
+};
+
+fn statements(sql: &str) -> impl Iterator<Item = &str> {
+    sql.split(';')
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+}
+
+async fn record(glue: &mut Database, migration: &Migration) -> Result<()> {
+    let applied_at = instant::SystemTime::now()
+        .duration_since(instant::SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    repository::execute_async(
+        glue,
+        &Query::new(include_str!("queries/migrations/record.sql"))
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(applied_at),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Applies `migration` and records it in one transaction, so one that fails halfway leaves the
+/// database as it was.
+async fn apply(glue: &mut Database, migration: &Migration) -> Result<()> {
+    glue.execute_async("BEGIN").await?;
+    let applied = async {
+        for statement in statements(migration.sql) {
+            glue.execute_async(statement).await?;
+        }
+        record(glue, migration).await
+    }
+    .await;
+    match applied {
+        Ok(()) => {
+            glue.execute_async("COMMIT").await?;
+            Ok(())
+        }
+        Err(e) => {
+            glue.execute_async("ROLLBACK").await?;
+            Err(e)
+        }
+    }
+}
+
+/// Whether a query failed because its table doesn't exist.
+fn table_missing<T>(result: &gluesql::result::Result<T>) -> bool {
+    matches!(
+        result,
+        Err(gluesql::result::Error::Fetch(FetchError::TableNotFound(_)))
+    )
+}
+
+/// Applies the migrations of `schema` that haven't been applied to `glue` yet. Returns the schema version.
+pub async fn run(glue: &mut Database, schema: &Schema) -> Result<i64> {
+    let versions = glue.execute_async("SELECT version FROM 'migrations'").await;
+    let mut version = if table_missing(&versions) {
+        glue.execute_async(include_str!("queries/migrations/initialize.sql"))
+            .await?;
+        let baseline = glue
+            .execute_async(&format!("SELECT * FROM '{}'", schema.baseline_table))
+            .await;
+        match schema.migrations.first() {
+            Some(first) if !table_missing(&baseline) => {
+                baseline?;
+                record(glue, first).await?;
+                first.version
+            }
+            _ => 0,
+        }
+    } else {
+        match versions? {
+            Payload::Select { rows, .. } => rows
+                .iter()
+                .filter_map(|row| match row.first() {
+                    Some(Value::I64(version)) => Some(*version),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0),
+            payload => {
+                return Err(repository::Error::UnexpectedPayload(format!(
+                    "{:?}",
+                    payload
+                )))
+            }
+        }
+    };
+
+    for migration in schema.migrations.iter().filter(|m| m.version > version) {
+        apply(glue, migration).await?;
+        version = migration.version;
+    }
+    Ok(version)
+}
+
+#[test]
+fn test_statements() {
+    assert_eq!(
+        statements("CREATE TABLE a (x INTEGER);\n\nINSERT INTO a VALUES (1);\n")
+            .collect::<Vec<_>>(),
+        vec!["CREATE TABLE a (x INTEGER)", "INSERT INTO a VALUES (1)"]
+    );
+}
+
+#[test]
+fn test_versions() {
+    for schema in [SCORES, CHARTS] {
+        for (idx, migration) in schema.migrations.iter().enumerate() {
+            assert_eq!(migration.version, idx as i64 + 1, "{}", migration.name);
+        }
+    }
+}
+
+#[test]
+fn test_scores_upgrade() {
+    use crate::promise::PromiseExecutor;
+    use gluesql::{
+        prelude::Glue,
+        sled_storage::{sled, SledStorage},
+    };
+
+    let storage = SledStorage::try_from(sled::Config::new().temporary(true)).unwrap();
+    let mut glue = Glue::new(storage);
+    glue.execute(include_str!("queries/migrations/scores/001_initial.sql"))
+        .unwrap();
+    glue.execute("INSERT INTO 'scores' VALUES (1, 120, 3, 250000, 80)")
+        .unwrap();
+
+    let mut exec = PromiseExecutor::new();
+    let promise = exec.spawn(async move {
+        let version = run(&mut glue, &SCORES).await;
+        (glue, version)
+    });
+    exec.poll();
+    let (mut glue, version) = exec.try_get(&promise).unwrap();
+    assert_eq!(version.unwrap(), SCORES.migrations.len() as i64);
+
+    let columns = crate::rulesets::catch::JUDGEMENT_COLUMNS.join(", ");
+    match glue
+        .execute(&format!(
+            "SELECT diff_id, {}, score, top_combo, mods, model, grade, pp, played_at, length, \
+             username, passed, replay, diff_hash FROM 'scores'",
+            columns
+        ))
+        .unwrap()
+    {
+        Payload::Select { rows, .. } => {
+            assert_eq!(
+                rows,
+                vec![vec![
+                    Value::I64(1),
+                    Value::I64(120),
+                    Value::I64(0),
+                    Value::I64(0),
+                    Value::I64(0),
+                    Value::I64(0),
+                    Value::I64(0),
+                    Value::I64(3),
+                    Value::I64(250000),
+                    Value::I64(80),
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                ]]
+            );
+        }
+        payload => panic!("{:?}", payload),
+    }
+}
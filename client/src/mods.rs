@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+/// A gameplay modifier. Stored with every score so plays under different mods can be told apart.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Mod {
+    /// Changes the playback rate of the music, range \[0.5; 2.0\].
+    Rate(f32),
+    /// The map can't be failed.
+    NoFail,
+    /// Fruits fade out before reaching the catcher.
+    Hidden,
+    /// Only the area around the catcher is visible.
+    Flashlight,
+}
+
+impl Mod {
+    /// Score multiplier for this mod.
+    pub fn multiplier(&self) -> f32 {
+        match *self {
+            // Linear from 0.3x at half speed to 1.0x at normal speed, then slowly rising.
+            Mod::Rate(rate) if rate < 1.0 => 0.3 + 0.7 * (rate - 0.5) / 0.5,
+            Mod::Rate(rate) => 1.0 + (rate - 1.0) * 0.24,
+            Mod::NoFail => 0.5,
+            Mod::Hidden => 1.06,
+            Mod::Flashlight => 1.12,
+        }
+    }
+
+    /// Short name displayed on leaderboard entries.
+    pub fn acronym(&self) -> String {
+        match *self {
+            Mod::Rate(rate) => format!("{:.2}x", rate),
+            Mod::NoFail => "NF".to_owned(),
+            Mod::Hidden => "HD".to_owned(),
+            Mod::Flashlight => "FL".to_owned(),
+        }
+    }
+}
+
+/// Combined score multiplier of all `mods`.
+pub fn multiplier(mods: &[Mod]) -> f32 {
+    mods.iter().map(Mod::multiplier).product()
+}
+
+/// Playback rate set by the [`Mod::Rate`] in `mods`, 1.0 if there is none.
+pub fn rate(mods: &[Mod]) -> f32 {
+    mods.iter()
+        .find_map(|m| match m {
+            Mod::Rate(rate) => Some(*rate),
+            _ => None,
+        })
+        .unwrap_or(1.)
+}
+
+/// Mod badges joined together, e.g. "HD FL 1.50x". Empty if no mods are active.
+pub fn badges(mods: &[Mod]) -> String {
+    mods.iter().map(Mod::acronym).collect::<Vec<_>>().join(" ")
+}
+
+#[test]
+fn test_multiplier() {
+    assert_eq!(multiplier(&[]), 1.0);
+    assert_eq!(multiplier(&[Mod::Rate(1.0)]), 1.0);
+    assert_eq!(multiplier(&[Mod::Rate(0.5)]), 0.3);
+    assert!((multiplier(&[Mod::Rate(2.0)]) - 1.24).abs() < f32::EPSILON);
+    assert_eq!(multiplier(&[Mod::NoFail, Mod::Hidden]), 0.53);
+}
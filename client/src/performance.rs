@@ -0,0 +1,132 @@
+use crate::mods::{self, Mod};
+
+/// Everything about a play that affects how much performance it's worth.
+#[derive(Debug, Clone)]
+pub struct PerformanceInput<'a> {
+    /// Star rating of the chart with `mods` applied, see [`crate::difficulty`].
+    pub star_rating: f32,
+    /// Time in seconds it takes a fruit to fall across the screen at normal speed.
+    pub fall_time: f32,
+    pub accuracy: f32,
+    pub top_combo: u32,
+    pub max_combo: u32,
+    pub misses: u32,
+    pub mods: &'a [Mod],
+}
+
+/// Converts the time it takes a fruit to fall, in seconds, to an osu! approach rate.
+pub fn approach_rate(fall_time: f32) -> f32 {
+    let ms = fall_time * 1000.;
+    if ms > 1200. {
+        (1800. - ms) / 120.
+    } else {
+        (1200. - ms) / 150. + 5.
+    }
+}
+
+/// Calculates the performance points (pp) a play is worth.
+pub fn calculate(input: &PerformanceInput) -> f32 {
+    let ar = approach_rate(input.fall_time / mods::rate(input.mods));
+
+    let mut value = (5. * (input.star_rating / 0.0049).max(1.) - 4.).powi(2) / 100_000.;
+
+    // Longer maps are harder to play consistently.
+    let length_bonus = 0.95
+        + 0.3 * (input.max_combo as f32 / 2500.).min(1.)
+        + if input.max_combo > 2500 {
+            (input.max_combo as f32 / 2500.).log10() * 0.475
+        } else {
+            0.
+        };
+    value *= length_bonus;
+
+    value *= 0.97f32.powi(input.misses as i32);
+    if input.max_combo > 0 {
+        value *= (input.top_combo as f32 / input.max_combo as f32)
+            .powf(0.8)
+            .min(1.);
+    }
+
+    // Both very high and very low approach rates are harder to read.
+    value *= if ar > 9. {
+        1. + 0.1 * (ar - 9.)
+    } else if ar < 8. {
+        1. + 0.025 * (8. - ar)
+    } else {
+        1.
+    };
+
+    for m in input.mods {
+        value *= match m {
+            Mod::Hidden => 1.05 + 0.075 * (10. - ar.min(10.)),
+            Mod::Flashlight => 1.35 * length_bonus,
+            Mod::NoFail => 0.9,
+            Mod::Rate(_) => 1.,
+        };
+    }
+
+    value * input.accuracy.powf(5.5)
+}
+
+#[test]
+fn test_calculate() {
+    let input = PerformanceInput {
+        star_rating: 5.,
+        fall_time: 0.9,
+        accuracy: 1.,
+        top_combo: 1000,
+        max_combo: 1000,
+        misses: 0,
+        mods: &[],
+    };
+    let base = calculate(&input);
+    assert!(base > 0.);
+
+    // Anything that makes the play worse lowers the value.
+    assert!(
+        calculate(&PerformanceInput {
+            accuracy: 0.95,
+            ..input.clone()
+        }) < base
+    );
+    assert!(
+        calculate(&PerformanceInput {
+            misses: 1,
+            top_combo: 600,
+            ..input.clone()
+        }) < base
+    );
+    assert!(
+        calculate(&PerformanceInput {
+            mods: &[Mod::NoFail],
+            ..input.clone()
+        }) < base
+    );
+
+    // Harder plays are worth more.
+    assert!(
+        calculate(&PerformanceInput {
+            star_rating: 6.,
+            ..input.clone()
+        }) > base
+    );
+    assert!(
+        calculate(&PerformanceInput {
+            mods: &[Mod::Hidden],
+            ..input.clone()
+        }) > base
+    );
+    assert!(
+        calculate(&PerformanceInput {
+            mods: &[Mod::Rate(1.5)],
+            ..input
+        }) > base
+    );
+}
+
+#[test]
+fn test_approach_rate() {
+    assert_eq!(approach_rate(1.5), 2.5);
+    assert_eq!(approach_rate(1.2), 5.);
+    assert_eq!(approach_rate(0.75), 8.);
+}
@@ -0,0 +1,88 @@
+use crate::chart::Chart;
+
+/// Time in seconds before the first fruit of a section starts falling that each loop starts from.
+const LEAD_IN: f32 = 1.;
+
+/// Part of a chart that's played over and over in practice mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Section {
+    /// Chart time the section starts at, in seconds.
+    pub start: f32,
+    /// Chart time the section ends at, in seconds.
+    pub end: f32,
+}
+
+impl Section {
+    /// Indices of the fruits of `chart` inside the section, in chart order.
+    pub fn fruits(&self, chart: &Chart) -> Vec<usize> {
+        chart
+            .fruits
+            .iter()
+            .enumerate()
+            .filter(|(_, fruit)| fruit.time >= self.start && fruit.time <= self.end)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Music position each loop starts from, leaving time for the first fruit to fall.
+    pub fn lead_in(&self, chart: &Chart) -> f32 {
+        (self.start - chart.fall_time - LEAD_IN).max(0.)
+    }
+}
+
+/// Stats of one pass through a [`Section`].
+#[derive(Debug, Clone, Copy)]
+pub struct LoopStats {
+    pub accuracy: f32,
+    pub misses: u32,
+    pub top_combo: u32,
+    pub max_combo: u32,
+    /// The player ran out of HP before the end of the section.
+    pub failed: bool,
+}
+
+#[test]
+fn test_section() {
+    use crate::chart::{Additions, Fruit, FruitKind};
+    use macroquad::prelude::WHITE;
+
+    let chart = Chart {
+        fruits: (0..10)
+            .map(|idx| Fruit {
+                position: 256.,
+                time: idx as f32,
+                hyper: None,
+                kind: FruitKind::Fruit,
+                additions: Additions {
+                    whistle: false,
+                    finish: false,
+                    clap: false,
+                },
+                color: WHITE,
+                plate_reset: false,
+                fall_multiplier: 1.,
+            })
+            .collect(),
+        events: Vec::new(),
+        fall_time: 1.,
+        fruit_radius: 32.,
+        catcher_width: 100.,
+        hp_drain: 5.,
+    };
+
+    let section = Section {
+        start: 3.,
+        end: 5.5,
+    };
+    assert_eq!(section.fruits(&chart), vec![3, 4, 5]);
+    assert_eq!(section.lead_in(&chart), 1.);
+    // Sections at the start of the chart can't start before the music does.
+    assert_eq!(
+        Section {
+            start: 0.5,
+            end: 2.
+        }
+        .lead_in(&chart),
+        0.
+    );
+}
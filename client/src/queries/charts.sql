@@ -0,0 +1,3 @@
+SELECT id, title
+    FROM 'charts'
+    ORDER BY title;
\ No newline at end of file
@@ -0,0 +1,2 @@
+DELETE FROM 'charts'
+    WHERE id = ?;
\ No newline at end of file
@@ -0,0 +1,2 @@
+DELETE FROM 'difficulties'
+    WHERE chart_id = ?;
\ No newline at end of file
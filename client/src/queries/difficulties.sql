@@ -0,0 +1,4 @@
+SELECT chart_id, id, title, hash, size, modified
+    FROM 'difficulties'
+    WHERE chart_id = ?
+    ORDER BY title;
\ No newline at end of file
@@ -0,0 +1,2 @@
+SELECT *
+    FROM 'scores';
\ No newline at end of file
@@ -0,0 +1,2 @@
+INSERT INTO 'charts'
+    VALUES (?, ?);
\ No newline at end of file
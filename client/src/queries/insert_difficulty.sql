@@ -0,0 +1,2 @@
+INSERT INTO 'difficulties'
+    VALUES (?, ?, ?, ?, ?, ?);
\ No newline at end of file
@@ -1,2 +1,23 @@
-INSERT INTO 'scores'
-    VALUES ({}, {}, {}, {}, {});
\ No newline at end of file
+INSERT INTO 'scores' (
+    diff_id,
+    fruit_count,
+    droplet_count,
+    tiny_droplet_count,
+    tiny_droplet_miss_count,
+    banana_count,
+    banana_miss_count,
+    miss_count,
+    score,
+    top_combo,
+    mods,
+    model,
+    grade,
+    pp,
+    played_at,
+    length,
+    username,
+    passed,
+    replay,
+    diff_hash
+)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);
\ No newline at end of file
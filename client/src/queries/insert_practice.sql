@@ -0,0 +1,2 @@
+INSERT INTO 'practice'
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?);
\ No newline at end of file
@@ -0,0 +1,3 @@
+UPDATE 'scores'
+    SET diff_hash = ?
+    WHERE diff_id = ? AND diff_hash IS NULL;
\ No newline at end of file
@@ -1,4 +1,4 @@
 SELECT *
     FROM 'scores'
-    WHERE diff_id = {}
+    WHERE diff_hash = ?
     ORDER BY score DESC;
\ No newline at end of file
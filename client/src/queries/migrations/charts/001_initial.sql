@@ -12,7 +12,3 @@ CREATE TABLE IF NOT EXISTS 'difficulties' (
       FOREIGN KEY(chart_id)
 	  REFERENCES charts(id)
 );
-
-INSERT INTO charts VALUES (1, "Kizuato");
-INSERT INTO difficulties VALUES(1, 1, "Platter");
-INSERT INTO difficulties VALUES(1, 2, "Ascendance's Rain");
\ No newline at end of file
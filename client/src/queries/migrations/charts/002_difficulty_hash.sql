@@ -0,0 +1 @@
+ALTER TABLE 'difficulties' ADD COLUMN hash TEXT NULL;
\ No newline at end of file
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS 'next_ids' (
+    chart_id INTEGER NOT NULL,
+    diff_id INTEGER NOT NULL,
+);
+
+INSERT INTO 'next_ids' VALUES (1, 1);
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE 'difficulties' ADD COLUMN size INTEGER NULL;
+ALTER TABLE 'difficulties' ADD COLUMN modified INTEGER NULL;
\ No newline at end of file
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS 'migrations' (
+    version INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    applied_at INTEGER NOT NULL,
+);
\ No newline at end of file
@@ -0,0 +1,2 @@
+INSERT INTO 'migrations'
+    VALUES (?, ?, ?);
\ No newline at end of file
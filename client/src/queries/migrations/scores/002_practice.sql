@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS 'practice' (
+    diff_id INTEGER,
+    start_time FLOAT,
+    end_time FLOAT,
+    loop_idx INTEGER,
+    accuracy FLOAT,
+    miss_count INTEGER,
+    top_combo INTEGER,
+    max_combo INTEGER,
+    failed BOOLEAN,
+);
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE 'scores' ADD COLUMN username TEXT NULL;
+ALTER TABLE 'scores' ADD COLUMN passed BOOLEAN NULL;
+ALTER TABLE 'scores' ADD COLUMN replay TEXT NULL;
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE 'scores' ADD COLUMN diff_hash TEXT NULL;
+ALTER TABLE 'practice' ADD COLUMN diff_hash TEXT NULL;
\ No newline at end of file
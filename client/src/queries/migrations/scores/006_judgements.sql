@@ -0,0 +1,6 @@
+ALTER TABLE 'scores' RENAME COLUMN hit_count TO fruit_count;
+ALTER TABLE 'scores' ADD COLUMN droplet_count INTEGER DEFAULT 0;
+ALTER TABLE 'scores' ADD COLUMN tiny_droplet_count INTEGER DEFAULT 0;
+ALTER TABLE 'scores' ADD COLUMN tiny_droplet_miss_count INTEGER DEFAULT 0;
+ALTER TABLE 'scores' ADD COLUMN banana_count INTEGER DEFAULT 0;
+ALTER TABLE 'scores' ADD COLUMN banana_miss_count INTEGER DEFAULT 0;
\ No newline at end of file
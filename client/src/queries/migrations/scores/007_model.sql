@@ -0,0 +1 @@
+ALTER TABLE 'scores' ADD COLUMN model TEXT NULL;
\ No newline at end of file
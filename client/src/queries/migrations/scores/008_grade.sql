@@ -0,0 +1 @@
+ALTER TABLE 'scores' ADD COLUMN grade TEXT NULL;
\ No newline at end of file
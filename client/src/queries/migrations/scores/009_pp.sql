@@ -0,0 +1 @@
+ALTER TABLE 'scores' ADD COLUMN pp FLOAT NULL;
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE 'scores' ADD COLUMN played_at INTEGER NULL;
+ALTER TABLE 'scores' ADD COLUMN length FLOAT NULL;
\ No newline at end of file
@@ -0,0 +1,2 @@
+SELECT chart_id, diff_id
+    FROM 'next_ids';
\ No newline at end of file
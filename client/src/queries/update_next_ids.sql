@@ -0,0 +1,2 @@
+UPDATE 'next_ids'
+    SET chart_id = ?, diff_id = ?;
\ No newline at end of file
@@ -0,0 +1,189 @@
+//! Saved replays. Stored in `data/replays` on native and in IndexedDB on web.
+
+use crate::library;
+#[cfg(target_family = "wasm")]
+use crate::repository;
+use instant::SystemTime;
+use serde::Serialize;
+
+/// File extension of saved replays.
+pub const EXTENSION: &str = "crp";
+
+#[cfg(not(target_family = "wasm"))]
+const REPLAY_DIRECTORY: &str = "data/replays";
+
+/// Names of the replays saved in browser storage, stored in local storage.
+#[cfg(target_family = "wasm")]
+const STORAGE_INDEX: &str = "replays";
+
+/// Name for the replay of a play of `title` \[`difficulty`\] started at `start`, without extension.
+pub fn replay_name(title: &str, difficulty: &str, start: SystemTime) -> String {
+    let date_time = time::OffsetDateTime::from_unix_timestamp(
+        start
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64,
+    )
+    .unwrap();
+    let date = date_time
+        .format(
+            &time::format_description::parse(
+                "[year repr:full] [month repr:short padding:zero] [day] [hour repr:24 padding:zero]-[minute padding:zero]",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+    library::sanitize(&format!("{} - {} ({})", title, difficulty, date))
+}
+
+/// Encodes a replay the way it's saved.
+pub fn encode<T: Serialize>(replay: &T) -> Vec<u8> {
+    let mut content = Vec::new();
+    replay
+        .serialize(&mut rmp_serde::Serializer::new(&mut content).with_binary())
+        .unwrap();
+    content
+}
+
+/// `name` with a number appended if it's already taken, e.g. "name (2)".
+fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_owned();
+    }
+    (2..)
+        .map(|idx| format!("{} ({})", name, idx))
+        .find(|candidate| !taken(candidate))
+        .unwrap()
+}
+
+/// Adds a replay to the library without overwriting existing ones. Returns the name it was saved as.
+#[cfg(not(target_family = "wasm"))]
+pub async fn store(name: &str, content: &[u8]) -> std::io::Result<String> {
+    use std::{io::Write, path::Path};
+
+    std::fs::create_dir_all(REPLAY_DIRECTORY)?;
+    let path = |name: &str| Path::new(REPLAY_DIRECTORY).join(format!("{}.{}", name, EXTENSION));
+    loop {
+        let name = unique_name(name, |name| path(name).exists());
+        // Something else could have created the file in the meantime, so only create it if it doesn't exist.
+        match std::fs::OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(path(&name))
+        {
+            Ok(mut file) => {
+                file.write_all(content)?;
+                return Ok(name);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Names of every saved replay.
+#[cfg(not(target_family = "wasm"))]
+pub fn list() -> Vec<String> {
+    let entries = match std::fs::read_dir(REPLAY_DIRECTORY) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut names = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != EXTENSION {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_owned())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub async fn load(name: &str) -> Option<Vec<u8>> {
+    std::fs::read(std::path::Path::new(REPLAY_DIRECTORY).join(format!("{}.{}", name, EXTENSION)))
+        .ok()
+}
+
+/// Makes a stored replay available outside the game. Replays are already files on native.
+#[cfg(not(target_family = "wasm"))]
+pub fn export(_name: &str, _content: &[u8]) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Adds a replay to the library in IndexedDB, which has room for far more than local storage.
+/// Returns the name it was saved as.
+#[cfg(target_family = "wasm")]
+pub async fn store(name: &str, content: &[u8]) -> std::io::Result<String> {
+    let mut names = list();
+    let name = unique_name(name, |name| names.iter().any(|taken| taken == name));
+    repository::store_file_local(&storage_key(&name), content)
+        .await
+        .map_err(storage_error)?;
+    names.push(name.clone());
+    repository::store_local(STORAGE_INDEX, &names).map_err(storage_error)?;
+    Ok(name)
+}
+
+/// Makes a stored replay available outside the game by downloading it.
+#[cfg(target_family = "wasm")]
+pub fn export(name: &str, content: &[u8]) -> std::io::Result<()> {
+    download(&format!("{}.{}", name, EXTENSION), content)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e)))
+}
+
+#[cfg(target_family = "wasm")]
+pub fn list() -> Vec<String> {
+    repository::load_local(STORAGE_INDEX)
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+#[cfg(target_family = "wasm")]
+pub async fn load(name: &str) -> Option<Vec<u8>> {
+    repository::load_file_local(&storage_key(name))
+        .await
+        .ok()
+        .flatten()
+}
+
+#[cfg(target_family = "wasm")]
+pub(crate) fn storage_key(name: &str) -> String {
+    format!("replay/{}", name)
+}
+
+#[cfg(target_family = "wasm")]
+fn storage_error(e: repository::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+}
+
+/// Makes the browser download `content` as a file called `file_name`.
+#[cfg(target_family = "wasm")]
+fn download(file_name: &str, content: &[u8]) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(content));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+    let document = web_sys::window().unwrap().document().unwrap();
+    let anchor = document
+        .create_element("a")?
+        .dyn_into::<web_sys::HtmlAnchorElement>()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    web_sys::Url::revoke_object_url(&url)
+}
+
+#[test]
+fn test_unique_name() {
+    let taken = ["replay", "replay (2)"];
+    assert_eq!(unique_name("other", |name| taken.contains(&name)), "other");
+    assert_eq!(
+        unique_name("replay", |name| taken.contains(&name)),
+        "replay (3)"
+    );
+}
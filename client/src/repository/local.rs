@@ -0,0 +1,119 @@
+//! Browser storage, used on web instead of GlueSQL. Values are stored as JSON in local storage,
+//! files in IndexedDB.
+
+use super::{Error, Result};
+use wasm_bindgen::{prelude::*, JsCast};
+
+/// Reads a value stored with [`store_local`], `None` if nothing is stored under `key`.
+pub fn load_local<T: serde::de::DeserializeOwned>(key: &str) -> Result<Option<T>> {
+    let value = match local_storage()?
+        .get_item(key)
+        .map_err(|e| Error::Storage(format!("{:?}", e)))?
+    {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    serde_json::from_str(&value)
+        .map(Some)
+        .map_err(|e| Error::Storage(e.to_string()))
+}
+
+/// Stores a value in the browser's local storage, which keeps it across reloads.
+pub fn store_local<T: serde::Serialize>(key: &str, value: &T) -> Result<()> {
+    let value = serde_json::to_string(value).map_err(|e| Error::Storage(e.to_string()))?;
+    local_storage()?
+        .set_item(key, &value)
+        .map_err(|e| Error::Storage(format!("{:?}", e)))
+}
+
+/// Removes whatever is stored under `key` from local storage.
+pub fn remove_local(key: &str) -> Result<()> {
+    local_storage()?
+        .remove_item(key)
+        .map_err(|e| Error::Storage(format!("{:?}", e)))
+}
+
+fn local_storage() -> Result<web_sys::Storage> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| Error::Storage("local storage is unavailable".to_owned()))
+}
+
+/// IndexedDB database that holds files, which are too big for local storage.
+const DATABASE: &str = "ctb";
+const FILES_STORE: &str = "files";
+
+/// Reads a file stored with [`store_file_local`], `None` if nothing is stored under `key`.
+pub async fn load_file_local(key: &str) -> Result<Option<Vec<u8>>> {
+    let store = database()
+        .await?
+        .transaction_with_str(FILES_STORE)
+        .and_then(|transaction| transaction.object_store(FILES_STORE))
+        .map_err(js_error)?;
+    let value = wait(&store.get(&JsValue::from_str(key)).map_err(js_error)?).await?;
+    Ok((!value.is_undefined()).then(|| js_sys::Uint8Array::new(&value).to_vec()))
+}
+
+/// Stores a file in the browser's IndexedDB, which keeps it across reloads.
+pub async fn store_file_local(key: &str, content: &[u8]) -> Result<()> {
+    let store = database()
+        .await?
+        .transaction_with_str_and_mode(FILES_STORE, web_sys::IdbTransactionMode::Readwrite)
+        .and_then(|transaction| transaction.object_store(FILES_STORE))
+        .map_err(js_error)?;
+    let request = store
+        .put_with_key(&js_sys::Uint8Array::from(content), &JsValue::from_str(key))
+        .map_err(js_error)?;
+    wait(&request).await?;
+    Ok(())
+}
+
+async fn database() -> Result<web_sys::IdbDatabase> {
+    let factory = web_sys::window()
+        .and_then(|window| window.indexed_db().ok().flatten())
+        .ok_or_else(|| Error::Storage("IndexedDB is unavailable".to_owned()))?;
+    let request = factory.open_with_u32(DATABASE, 1).map_err(js_error)?;
+
+    // Runs when the database is created.
+    let request_c = request.clone();
+    let onupgradeneeded_cb = Closure::wrap(Box::new(move |_e: web_sys::Event| {
+        if let Ok(database) = request_c.result() {
+            let database = database.unchecked_into::<web_sys::IdbDatabase>();
+            database.create_object_store(FILES_STORE).unwrap();
+        }
+    }) as Box<dyn FnMut(web_sys::Event)>);
+    request.set_onupgradeneeded(Some(onupgradeneeded_cb.as_ref().unchecked_ref()));
+
+    let database = wait(&request).await?;
+    request.set_onupgradeneeded(None);
+    Ok(database.unchecked_into())
+}
+
+/// Waits until `request` succeeds or fails.
+async fn wait(request: &web_sys::IdbRequest) -> Result<JsValue> {
+    let (tx, rx) = flume::bounded(1);
+
+    let request_c = request.clone();
+    let success_tx = tx.clone();
+    let onsuccess_cb = Closure::wrap(Box::new(move |_e: web_sys::Event| {
+        let _ = success_tx.send(request_c.result().map_err(js_error));
+    }) as Box<dyn FnMut(web_sys::Event)>);
+    request.set_onsuccess(Some(onsuccess_cb.as_ref().unchecked_ref()));
+
+    let onerror_cb = Closure::wrap(Box::new(move |_e: web_sys::Event| {
+        let _ = tx.send(Err(Error::Storage("IndexedDB request failed".to_owned())));
+    }) as Box<dyn FnMut(web_sys::Event)>);
+    request.set_onerror(Some(onerror_cb.as_ref().unchecked_ref()));
+
+    let result = rx
+        .recv_async()
+        .await
+        .map_err(|e| Error::Storage(e.to_string()))?;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    result
+}
+
+fn js_error(e: JsValue) -> Error {
+    Error::Storage(format!("{:?}", e))
+}
@@ -0,0 +1,34 @@
+//! Typed access to the local stores: GlueSQL on native and local storage on web.
+
+#[cfg(target_family = "wasm")]
+mod local;
+#[cfg(not(target_family = "wasm"))]
+mod sql;
+
+#[cfg(target_family = "wasm")]
+pub use local::*;
+#[cfg(not(target_family = "wasm"))]
+pub use sql::*;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[cfg(not(target_family = "wasm"))]
+    #[error("query failed: {0}")]
+    Sql(#[from] gluesql::result::Error),
+    #[error("query has {expected} placeholders but {bound} values were bound")]
+    Parameters { expected: usize, bound: usize },
+    #[error("expected rows, got {0}")]
+    UnexpectedPayload(String),
+    #[error("column {0} is missing")]
+    MissingColumn(String),
+    #[error("column {0} has an unexpected type")]
+    WrongType(String),
+    #[error("browser storage failed: {0}")]
+    Storage(String),
+    #[error("couldn't scan the chart library: {0}")]
+    Scan(String),
+    #[error("{0} has no music that can be played")]
+    MissingAudio(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
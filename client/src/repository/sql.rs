@@ -0,0 +1,415 @@
+//! GlueSQL stores, used on native. GlueSQL has no prepared statements, so values given to `?`
+//! placeholders are escaped by [`ToSql`] and spliced into the SQL string. Rows are mapped to
+//! structs with [`FromRow`].
+
+use super::{Error, Result};
+use crate::{identity::DifficultyHash, Database};
+use gluesql::prelude::{Payload, Value};
+
+/// A value that can fill a placeholder.
+pub trait ToSql {
+    /// The value as an SQL literal, with quotes in strings escaped.
+    fn to_sql(&self) -> String;
+}
+
+macro_rules! impl_to_sql_integer {
+    ($($ty:ty),*) => {
+        $(impl ToSql for $ty {
+            fn to_sql(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+impl_to_sql_integer!(u32, u64, i64, usize);
+
+impl ToSql for f32 {
+    fn to_sql(&self) -> String {
+        if self.is_finite() {
+            self.to_string()
+        } else {
+            "NULL".to_owned()
+        }
+    }
+}
+
+impl ToSql for bool {
+    fn to_sql(&self) -> String {
+        self.to_string().to_uppercase()
+    }
+}
+
+impl ToSql for str {
+    fn to_sql(&self) -> String {
+        format!("'{}'", self.replace('\'', "''"))
+    }
+}
+
+impl ToSql for String {
+    fn to_sql(&self) -> String {
+        self.as_str().to_sql()
+    }
+}
+
+impl ToSql for DifficultyHash {
+    fn to_sql(&self) -> String {
+        self.as_str().to_sql()
+    }
+}
+
+impl<T: ToSql + ?Sized> ToSql for &T {
+    fn to_sql(&self) -> String {
+        (**self).to_sql()
+    }
+}
+
+impl<T: ToSql> ToSql for Option<T> {
+    fn to_sql(&self) -> String {
+        match self {
+            Some(value) => value.to_sql(),
+            None => "NULL".to_owned(),
+        }
+    }
+}
+
+/// An SQL statement with `?` placeholders, filled in order by [`Query::bind`] when it's executed.
+#[derive(Debug, Clone)]
+pub struct Query {
+    sql: &'static str,
+    params: Vec<String>,
+}
+
+impl Query {
+    pub fn new(sql: &'static str) -> Self {
+        Query {
+            sql,
+            params: Vec::new(),
+        }
+    }
+
+    /// Fills the next placeholder with `value` as escaped by [`ToSql::to_sql`]. This is text
+    /// substitution, not parameter binding; the literal becomes part of the SQL string.
+    pub fn bind(mut self, value: impl ToSql) -> Self {
+        self.params.push(value.to_sql());
+        self
+    }
+
+    /// The statement with every placeholder replaced. Question marks inside string literals and
+    /// quoted names are left alone.
+    fn to_sql(&self) -> Result<String> {
+        let mut sql = String::with_capacity(self.sql.len());
+        let mut params = self.params.iter();
+        let mut expected = 0;
+        let mut quote = None;
+        for c in self.sql.chars() {
+            match (quote, c) {
+                (None, '\'' | '"') => quote = Some(c),
+                (Some(open), _) if open == c => quote = None,
+                (None, '?') => {
+                    expected += 1;
+                    if let Some(param) = params.next() {
+                        sql.push_str(param);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            sql.push(c);
+        }
+
+        if expected != self.params.len() {
+            return Err(Error::Parameters {
+                expected,
+                bound: self.params.len(),
+            });
+        }
+        Ok(sql)
+    }
+}
+
+/// A value that can be read from a column.
+pub trait FromSql: Sized {
+    /// `None` if the value has the wrong type.
+    fn from_sql(value: &Value) -> Option<Self>;
+}
+
+impl FromSql for i64 {
+    fn from_sql(value: &Value) -> Option<Self> {
+        match value {
+            Value::I64(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for u32 {
+    fn from_sql(value: &Value) -> Option<Self> {
+        i64::from_sql(value)?.try_into().ok()
+    }
+}
+
+impl FromSql for u64 {
+    fn from_sql(value: &Value) -> Option<Self> {
+        i64::from_sql(value)?.try_into().ok()
+    }
+}
+
+impl FromSql for f32 {
+    fn from_sql(value: &Value) -> Option<Self> {
+        match value {
+            Value::F64(value) => Some(*value as f32),
+            Value::I64(value) => Some(*value as f32),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for bool {
+    fn from_sql(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for String {
+    fn from_sql(value: &Value) -> Option<Self> {
+        match value {
+            Value::Str(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for DifficultyHash {
+    fn from_sql(value: &Value) -> Option<Self> {
+        String::from_sql(value).map(Into::into)
+    }
+}
+
+/// NULL is read as `None`.
+impl<T: FromSql> FromSql for Option<T> {
+    fn from_sql(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_sql(value).map(Some),
+        }
+    }
+}
+
+/// A selected row, with values looked up by column name.
+pub struct Row<'a> {
+    labels: &'a [String],
+    values: &'a [Value],
+}
+
+impl<'a> Row<'a> {
+    pub fn get<T: FromSql>(&self, column: &str) -> Result<T> {
+        let idx = self
+            .labels
+            .iter()
+            .position(|label| label == column)
+            .ok_or_else(|| Error::MissingColumn(column.to_owned()))?;
+        T::from_sql(&self.values[idx]).ok_or_else(|| Error::WrongType(column.to_owned()))
+    }
+}
+
+/// Something that can be built from a selected row.
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self>;
+}
+
+fn rows<T: FromRow>(payload: Payload) -> Result<Vec<T>> {
+    match payload {
+        Payload::Select { labels, rows } => rows
+            .iter()
+            .map(|values| {
+                T::from_row(&Row {
+                    labels: &labels,
+                    values,
+                })
+            })
+            .collect(),
+        payload => Err(Error::UnexpectedPayload(format!("{:?}", payload))),
+    }
+}
+
+pub fn execute(glue: &mut Database, query: &Query) -> Result<Payload> {
+    Ok(glue.execute(query.to_sql()?)?)
+}
+
+pub async fn execute_async(glue: &mut Database, query: &Query) -> Result<Payload> {
+    Ok(glue.execute_async(query.to_sql()?).await?)
+}
+
+pub fn select<T: FromRow>(glue: &mut Database, query: &Query) -> Result<Vec<T>> {
+    rows(execute(glue, query)?)
+}
+
+pub async fn select_async<T: FromRow>(glue: &mut Database, query: &Query) -> Result<Vec<T>> {
+    rows(execute_async(glue, query).await?)
+}
+
+/// A chart in the chart library, `data/.chart`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartRow {
+    pub id: u32,
+    pub title: String,
+}
+
+impl FromRow for ChartRow {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(ChartRow {
+            id: row.get("id")?,
+            title: row.get("title")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DifficultyRow {
+    pub chart_id: u32,
+    pub id: u32,
+    pub title: String,
+    /// Difficulties added before they were hashed don't have this until the next scan.
+    pub hash: Option<DifficultyHash>,
+    /// Size of the `.osu` file when it was hashed.
+    pub size: Option<u64>,
+    /// Modification time of the `.osu` file when it was hashed, in milliseconds since the Unix epoch.
+    pub modified: Option<u64>,
+}
+
+impl FromRow for DifficultyRow {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(DifficultyRow {
+            chart_id: row.get("chart_id")?,
+            id: row.get("id")?,
+            title: row.get("title")?,
+            hash: row.get("hash")?,
+            size: row.get("size")?,
+            modified: row.get("modified")?,
+        })
+    }
+}
+
+/// Ids the next new chart and difficulty get.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NextIdsRow {
+    pub chart_id: u32,
+    pub diff_id: u32,
+}
+
+impl FromRow for NextIdsRow {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(NextIdsRow {
+            chart_id: row.get("chart_id")?,
+            diff_id: row.get("diff_id")?,
+        })
+    }
+}
+
+pub fn charts(glue: &mut Database) -> Result<Vec<ChartRow>> {
+    select(glue, &Query::new(include_str!("../queries/charts.sql")))
+}
+
+pub fn difficulties(glue: &mut Database, chart_id: u32) -> Result<Vec<DifficultyRow>> {
+    select(
+        glue,
+        &Query::new(include_str!("../queries/difficulties.sql")).bind(chart_id),
+    )
+}
+
+pub fn insert_chart(glue: &mut Database, chart: &ChartRow) -> Result<()> {
+    execute(
+        glue,
+        &Query::new(include_str!("../queries/insert_chart.sql"))
+            .bind(chart.id)
+            .bind(&chart.title),
+    )?;
+    Ok(())
+}
+
+pub fn insert_difficulty(glue: &mut Database, difficulty: &DifficultyRow) -> Result<()> {
+    execute(
+        glue,
+        &Query::new(include_str!("../queries/insert_difficulty.sql"))
+            .bind(difficulty.chart_id)
+            .bind(difficulty.id)
+            .bind(&difficulty.title)
+            .bind(&difficulty.hash)
+            .bind(difficulty.size)
+            .bind(difficulty.modified),
+    )?;
+    Ok(())
+}
+
+pub fn next_ids(glue: &mut Database) -> Result<Option<NextIdsRow>> {
+    Ok(
+        select(glue, &Query::new(include_str!("../queries/next_ids.sql")))?
+            .into_iter()
+            .next(),
+    )
+}
+
+pub fn update_next_ids(glue: &mut Database, next_ids: &NextIdsRow) -> Result<()> {
+    execute(
+        glue,
+        &Query::new(include_str!("../queries/update_next_ids.sql"))
+            .bind(next_ids.chart_id)
+            .bind(next_ids.diff_id),
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_bind() {
+    let query = Query::new("SELECT * FROM 'scores?' WHERE diff_id = ? AND mods = ?")
+        .bind(3u32)
+        .bind("it's?");
+    assert_eq!(
+        query.to_sql().unwrap(),
+        "SELECT * FROM 'scores?' WHERE diff_id = 3 AND mods = 'it''s?'"
+    );
+
+    let query = Query::new("INSERT INTO t VALUES (?, ?, ?)")
+        .bind(Some(true))
+        .bind(None::<String>)
+        .bind(f32::NAN);
+    assert_eq!(
+        query.to_sql().unwrap(),
+        "INSERT INTO t VALUES (TRUE, NULL, NULL)"
+    );
+
+    assert!(matches!(
+        Query::new("SELECT ?").to_sql(),
+        Err(Error::Parameters {
+            expected: 1,
+            bound: 0
+        })
+    ));
+}
+
+#[test]
+fn test_row() {
+    let labels = ["id".to_owned(), "title".to_owned(), "replay".to_owned()];
+    let values = [Value::I64(1), Value::Str("Kizuato".to_owned()), Value::Null];
+    let row = Row {
+        labels: &labels,
+        values: &values,
+    };
+    assert_eq!(
+        ChartRow::from_row(&row).unwrap(),
+        ChartRow {
+            id: 1,
+            title: "Kizuato".to_owned()
+        }
+    );
+    assert_eq!(row.get::<Option<String>>("replay").unwrap(), None);
+    assert!(matches!(row.get::<String>("id"), Err(Error::WrongType(_))));
+    assert!(matches!(
+        row.get::<u32>("chart_id"),
+        Err(Error::MissingColumn(_))
+    ));
+}
@@ -1,6 +1,6 @@
 use super::{JudgementResult, Ruleset};
 use crate::{
-    chart::{Chart, Fruit},
+    chart::{Chart, Fruit, FruitKind},
     score::{Judgement, Score, ScoreRecorder},
 };
 use macroquad::prelude::*;
@@ -9,26 +9,96 @@ use macroquad::prelude::*;
     Debug, Hash, Eq, PartialEq, serde::Serialize, serde::Deserialize, Clone, PartialOrd, Ord,
 )]
 pub enum CatchJudgement {
-    Perfect,
+    Fruit,
+    Droplet,
+    TinyDroplet,
+    TinyDropletMiss,
+    Banana,
+    BananaMiss,
 }
 
+/// Database column names for judgement counts, in [`crate::score::judgement_counts`] order.
+pub const JUDGEMENT_COLUMNS: [&str; 7] = [
+    "fruit_count",
+    "droplet_count",
+    "tiny_droplet_count",
+    "tiny_droplet_miss_count",
+    "banana_count",
+    "banana_miss_count",
+    "miss_count",
+];
+
 pub struct CatchHitDetails {
     /// How far from the center it was hit \[-1; 1\].
     pub off: f32,
 }
 
-impl Judgement for CatchJudgement {
-    fn hit(_inaccuracy: f32) -> Self {
-        // There's no accuracy in Catch.
-        Self::Perfect
+impl CatchJudgement {
+    /// Whether the object was caught. Missed tiny droplets and bananas are judged as hits too.
+    pub fn caught(&self) -> bool {
+        matches!(
+            self,
+            CatchJudgement::Fruit
+                | CatchJudgement::Droplet
+                | CatchJudgement::TinyDroplet
+                | CatchJudgement::Banana
+        )
+    }
+}
+
+/// Where on the catcher the object of a judgement was caught, `None` if it wasn't.
+pub fn catch_offset(result: &JudgementResult<(CatchJudgement, CatchHitDetails)>) -> Option<f32> {
+    match result {
+        JudgementResult::Hit((judgement, details)) if judgement.caught() => Some(details.off),
+        _ => None,
     }
+}
 
+impl Judgement for CatchJudgement {
     fn weight(&self) -> f32 {
-        1.0
+        match self {
+            CatchJudgement::Fruit | CatchJudgement::Droplet | CatchJudgement::TinyDroplet => 1.0,
+            CatchJudgement::TinyDropletMiss
+            | CatchJudgement::Banana
+            | CatchJudgement::BananaMiss => 0.0,
+        }
+    }
+
+    fn max_weight(&self) -> f32 {
+        match self {
+            CatchJudgement::Fruit
+            | CatchJudgement::Droplet
+            | CatchJudgement::TinyDroplet
+            | CatchJudgement::TinyDropletMiss => 1.0,
+            // Bananas are a bonus and don't affect accuracy.
+            CatchJudgement::Banana | CatchJudgement::BananaMiss => 0.0,
+        }
+    }
+
+    fn affects_combo(&self) -> bool {
+        matches!(self, CatchJudgement::Fruit | CatchJudgement::Droplet)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            CatchJudgement::Fruit => "Fruits",
+            CatchJudgement::Droplet => "Droplets",
+            CatchJudgement::TinyDroplet => "Tiny Droplets",
+            CatchJudgement::TinyDropletMiss => "Tiny Droplet Misses",
+            CatchJudgement::Banana => "Bananas",
+            CatchJudgement::BananaMiss => "Banana Misses",
+        }
     }
 
     fn all() -> Vec<Self> {
-        vec![CatchJudgement::Perfect]
+        vec![
+            CatchJudgement::Fruit,
+            CatchJudgement::Droplet,
+            CatchJudgement::TinyDroplet,
+            CatchJudgement::TinyDropletMiss,
+            CatchJudgement::Banana,
+            CatchJudgement::BananaMiss,
+        ]
     }
 }
 
@@ -40,11 +110,14 @@ pub struct CatchSyncFrame {
     pub position: f32,
 }
 
-#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
 pub struct CatchInput {
     pub left: bool,
     pub right: bool,
     pub dash: bool,
+    /// Playfield position to move towards instead of moving left or right, for absolute input like the mouse.
+    #[serde(default)]
+    pub target: Option<f32>,
 }
 
 pub fn catcher_speed(dashing: bool, hyper_multiplier: f32) -> f32 {
@@ -100,8 +173,13 @@ impl Ruleset for CatchRuleset {
         }
 
         // Apply input to position and clamp the value.
-        input.left.then(|| self.position -= speed * dt);
-        input.right.then(|| self.position += speed * dt);
+        if let Some(target) = input.target {
+            let step = speed * dt;
+            self.position += (target - self.position).clamp(-step, step);
+        } else {
+            input.left.then(|| self.position -= speed * dt);
+            input.right.then(|| self.position += speed * dt);
+        }
         self.position = self.position.clamp(0.0, 512.0);
 
         // If we hit a hyperfruit, the multiplier needs to set.
@@ -135,19 +213,45 @@ impl Ruleset for CatchRuleset {
         let distance = object.position - self.position;
         let off = distance / chart.catcher_width;
 
-        if off.abs() <= 1. {
-            if current_height >= catcher_height && prev_height <= catcher_height {
-                return Some(JudgementResult::Hit((
-                    CatchJudgement::Perfect,
-                    CatchHitDetails { off },
-                )));
-            }
-
-            if current_height >= screen_height() {
-                return Some(JudgementResult::Miss);
-            }
+        if off.abs() <= 1. && current_height >= catcher_height && prev_height <= catcher_height {
+            let judgement = match object.kind {
+                FruitKind::Fruit => CatchJudgement::Fruit,
+                FruitKind::Droplet => CatchJudgement::Droplet,
+                FruitKind::TinyDroplet => CatchJudgement::TinyDroplet,
+                FruitKind::Banana => CatchJudgement::Banana,
+            };
+            return Some(JudgementResult::Hit((judgement, CatchHitDetails { off })));
+        }
+
+        if current_height >= screen_height() {
+            // Only fruits and droplets break combo when missed.
+            return Some(match object.kind {
+                FruitKind::Fruit | FruitKind::Droplet => JudgementResult::Miss,
+                FruitKind::TinyDroplet => {
+                    JudgementResult::Hit((CatchJudgement::TinyDropletMiss, CatchHitDetails { off }))
+                }
+                FruitKind::Banana => {
+                    JudgementResult::Hit((CatchJudgement::BananaMiss, CatchHitDetails { off }))
+                }
+            });
         }
 
         None
     }
 }
+
+#[test]
+fn test_catch_offset() {
+    let hit = |judgement: CatchJudgement, off: f32| {
+        JudgementResult::Hit((judgement, CatchHitDetails { off }))
+    };
+    assert_eq!(catch_offset(&hit(CatchJudgement::Fruit, 0.5)), Some(0.5));
+    assert_eq!(catch_offset(&hit(CatchJudgement::Banana, -0.2)), Some(-0.2));
+    // Missed objects are judged far outside the catcher.
+    assert_eq!(
+        catch_offset(&hit(CatchJudgement::TinyDropletMiss, 3.)),
+        None
+    );
+    assert_eq!(catch_offset(&hit(CatchJudgement::BananaMiss, -4.)), None);
+    assert_eq!(catch_offset(&JudgementResult::Miss), None);
+}
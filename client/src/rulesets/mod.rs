@@ -19,6 +19,16 @@ impl<H> JudgementResult<H> {
     }
 }
 
+impl<J: Judgement> JudgementResult<J> {
+    /// Display name, used on the result screen.
+    pub fn name(&self) -> &'static str {
+        match self {
+            JudgementResult::Hit(h) => h.name(),
+            JudgementResult::Miss => "Misses",
+        }
+    }
+}
+
 pub trait Ruleset {
     /// Input type for this ruleset.
     type Input: serde::Serialize + for<'a> serde::Deserialize<'a>;
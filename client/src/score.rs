@@ -1,11 +1,21 @@
 use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, hash::Hash};
 
-use crate::rulesets::JudgementResult;
+use crate::{
+    identity::DifficultyHash,
+    mods::{self, Mod},
+    rulesets::JudgementResult,
+};
 
 pub trait Judgement: Hash + Eq + Clone + PartialOrd + Ord {
-    fn hit(inaccuracy: f32) -> Self;
+    /// Weight this judgement contributes towards accuracy.
     fn weight(&self) -> f32;
+    /// Weight this judgement could have contributed towards accuracy at best.
+    fn max_weight(&self) -> f32;
+    /// Whether this judgement increases combo. Judgements that don't, don't break it either.
+    fn affects_combo(&self) -> bool;
+    /// Display name, used on the result screen.
+    fn name(&self) -> &'static str;
     fn all() -> Vec<Self>;
 }
 
@@ -14,18 +24,248 @@ pub fn accuracy<J: Judgement>(judgements: &BTreeMap<JudgementResult<J>, u32>) ->
         .iter()
         .map(|(judgement, &count)| if let JudgementResult::Hit(h) = judgement { h.weight() } else { 0. } * count as f32)
         .sum::<f32>();
-    let max_weight = judgements.iter().map(|(_, &count)| count).sum::<u32>() as f32;
-    weight_sum / max_weight
+    let max_weight = judgements
+        .iter()
+        .map(|(judgement, &count)| if let JudgementResult::Hit(h) = judgement { h.max_weight() } else { 1. } * count as f32)
+        .sum::<f32>();
+    if max_weight > 0. {
+        weight_sum / max_weight
+    } else {
+        1.
+    }
+}
+
+/// Judgement counts in [`Judgement::all`] order, followed by misses.
+pub fn judgement_counts<J: Judgement>(judgements: &BTreeMap<JudgementResult<J>, u32>) -> Vec<u32> {
+    J::all()
+        .into_iter()
+        .map(JudgementResult::Hit)
+        .chain(std::iter::once(JudgementResult::Miss))
+        .map(|judgement| judgements.get(&judgement).copied().unwrap_or(0))
+        .collect()
+}
+
+/// Inverse of [`judgement_counts`].
+pub fn judgements_from_counts<J: Judgement>(counts: &[u32]) -> BTreeMap<JudgementResult<J>, u32> {
+    J::all()
+        .into_iter()
+        .map(JudgementResult::Hit)
+        .chain(std::iter::once(JudgementResult::Miss))
+        .zip(counts.iter().copied())
+        .collect()
+}
+
+/// Formula used to turn judgements into a score.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ScoringModel {
+    /// Every hit is weighted by the combo it was hit at. Max = 1,000,000.
+    #[default]
+    ComboWeighted,
+    /// Similar to osu! classic scoring, 300 points per hit with a bonus that grows with combo. Unbounded.
+    Classic,
+    /// 30% combo and 70% accuracy, similar to osu!lazer standardised scoring. Max = 1,000,000.
+    Standardised,
+}
+
+impl ScoringModel {
+    pub const ALL: [ScoringModel; 3] = [
+        ScoringModel::ComboWeighted,
+        ScoringModel::Classic,
+        ScoringModel::Standardised,
+    ];
+
+    /// Display name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScoringModel::ComboWeighted => "Combo Weighted",
+            ScoringModel::Classic => "Classic",
+            ScoringModel::Standardised => "Standardised",
+        }
+    }
+
+    /// Identifier used when storing the model in databases.
+    pub fn key(&self) -> &'static str {
+        match self {
+            ScoringModel::ComboWeighted => "combo-weighted",
+            ScoringModel::Classic => "classic",
+            ScoringModel::Standardised => "standardised",
+        }
+    }
+
+    /// Inverse of [`Self::key`].
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|model| model.key() == key)
+    }
+
+    /// Score before mod multipliers are applied.
+    ///
+    /// `combo_sum` is the sum of the combo at every hit, `combo_hits` the number of hits that affected combo and `judged` the number of combo-affecting objects judged so far, hit or missed.
+    pub fn raw_score(
+        &self,
+        combo_sum: u64,
+        combo_hits: u32,
+        judged: u32,
+        max_combo: u32,
+        accuracy: f32,
+    ) -> f64 {
+        // Ratio of `combo_sum` to the `combo_sum` of a full combo.
+        let combo_ratio = combo_sum as f64 * 2. / (max_combo as f64 * (max_combo as f64 + 1.));
+        match self {
+            ScoringModel::ComboWeighted => combo_ratio * 1_000_000.,
+            ScoringModel::Classic => {
+                300. * combo_hits as f64 + 12. * (combo_sum as f64 - combo_hits as f64)
+            }
+            ScoringModel::Standardised => {
+                let judged_ratio = judged as f64 / max_combo as f64;
+                (0.3 * combo_ratio + 0.7 * accuracy as f64 * judged_ratio) * 1_000_000.
+            }
+        }
+    }
+
+    /// Estimate the score a play would have gotten under this model, for plays set with another model.
+    ///
+    /// The order of hits isn't stored so they are assumed to be split into streaks as long as `top_combo`.
+    pub fn estimate<J: Judgement>(
+        &self,
+        judgements: &BTreeMap<JudgementResult<J>, u32>,
+        top_combo: u32,
+        mods: &[Mod],
+    ) -> u32 {
+        let combo_hits = judgements
+            .iter()
+            .filter(
+                |(judgement, _)| matches!(judgement, JudgementResult::Hit(h) if h.affects_combo()),
+            )
+            .map(|(_, &count)| count)
+            .sum::<u32>();
+        let misses = judgements.get(&JudgementResult::Miss).copied().unwrap_or(0);
+        let max_combo = combo_hits + misses;
+
+        let streak = top_combo.max(1) as u64;
+        let rest = combo_hits as u64 % streak;
+        let combo_sum =
+            combo_hits as u64 / streak * streak * (streak + 1) / 2 + rest * (rest + 1) / 2;
+
+        (self.raw_score(
+            combo_sum,
+            combo_hits,
+            max_combo,
+            max_combo,
+            accuracy(judgements),
+        ) * mods::multiplier(mods) as f64)
+            .round() as u32
+    }
+}
+
+/// Letter grade of a play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Grade {
+    /// SS with Hidden or Flashlight.
+    SilverSS,
+    SS,
+    /// S with Hidden or Flashlight.
+    SilverS,
+    S,
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Grade {
+    pub const ALL: [Grade; 8] = [
+        Grade::SilverSS,
+        Grade::SS,
+        Grade::SilverS,
+        Grade::S,
+        Grade::A,
+        Grade::B,
+        Grade::C,
+        Grade::D,
+    ];
+
+    /// `accuracy` \[0; 1\], `misses` only counts misses that broke combo.
+    pub fn new(accuracy: f32, misses: u32, mods: &[Mod]) -> Self {
+        let silver = mods
+            .iter()
+            .any(|m| matches!(m, Mod::Hidden | Mod::Flashlight));
+        if accuracy >= 1. {
+            if silver {
+                Grade::SilverSS
+            } else {
+                Grade::SS
+            }
+        } else if accuracy > 0.98 && misses == 0 {
+            if silver {
+                Grade::SilverS
+            } else {
+                Grade::S
+            }
+        } else if accuracy > 0.94 {
+            Grade::A
+        } else if accuracy > 0.9 {
+            Grade::B
+        } else if accuracy > 0.85 {
+            Grade::C
+        } else {
+            Grade::D
+        }
+    }
+
+    /// Grade of a play from its judgements.
+    pub fn from_judgements<J: Judgement>(
+        judgements: &BTreeMap<JudgementResult<J>, u32>,
+        mods: &[Mod],
+    ) -> Self {
+        let misses = judgements.get(&JudgementResult::Miss).copied().unwrap_or(0);
+        Grade::new(accuracy(judgements), misses, mods)
+    }
+
+    /// Display name, silver grades share their name with the regular grade.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Grade::SilverSS | Grade::SS => "SS",
+            Grade::SilverS | Grade::S => "S",
+            Grade::A => "A",
+            Grade::B => "B",
+            Grade::C => "C",
+            Grade::D => "D",
+        }
+    }
+
+    /// Identifier used when storing the grade in databases.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Grade::SilverSS => "ss-silver",
+            Grade::SS => "ss",
+            Grade::SilverS => "s-silver",
+            Grade::S => "s",
+            Grade::A => "a",
+            Grade::B => "b",
+            Grade::C => "c",
+            Grade::D => "d",
+        }
+    }
+
+    /// Inverse of [`Self::key`].
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|grade| grade.key() == key)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Score<J: Judgement> {
     pub username: Option<String>,
-    pub diff_id: u32,
+    pub diff_hash: DifficultyHash,
     pub top_combo: u32,
     pub judgements: BTreeMap<JudgementResult<J>, u32>,
     pub score: u32,
     pub passed: bool,
+    pub mods: Vec<Mod>,
+    pub model: ScoringModel,
+    pub grade: Grade,
+    /// Performance points, see [`crate::performance`].
+    pub pp: f32,
 }
 
 pub struct ScoreRecorder<J: Judgement> {
@@ -36,16 +276,25 @@ pub struct ScoreRecorder<J: Judgement> {
     pub judgements: BTreeMap<JudgementResult<J>, u32>,
     pub weight_sum: f32,
 
-    /// This needs to be tracked separately due to floating point imprecision.
-    pub internal_score: f64,
+    /// Sum of the combo at every hit. Kept as an integer to avoid floating point imprecision.
+    pub combo_sum: u64,
+    /// Number of hits that affected combo.
+    pub combo_hits: u32,
+    /// Number of combo-affecting objects judged so far, hit or missed.
+    pub judged: u32,
     pub chain_miss_count: u32,
 
-    /// Max = 1,000,000
+    pub model: ScoringModel,
+    /// See [`ScoringModel`] for limits. Mod multipliers are applied.
     pub score: u32,
     /// [0, 1]
     pub accuracy: f32,
     /// [0, 1]
     pub hp: f32,
+
+    pub mods: Vec<Mod>,
+    /// Combined score multiplier of all active mods.
+    pub multiplier: f32,
 }
 
 fn polynomial(x: f32, coeffs: &[f32]) -> f32 {
@@ -53,7 +302,7 @@ fn polynomial(x: f32, coeffs: &[f32]) -> f32 {
 }
 
 impl<J: Judgement> ScoreRecorder<J> {
-    pub fn new(max_combo: u32) -> Self {
+    pub fn new(max_combo: u32, mods: Vec<Mod>, model: ScoringModel) -> Self {
         ScoreRecorder {
             combo: 0,
             top_combo: 0,
@@ -64,30 +313,37 @@ impl<J: Judgement> ScoreRecorder<J> {
                 .chain(std::iter::once((JudgementResult::Miss, 0)))
                 .collect(),
             weight_sum: 0.,
-            internal_score: 0.,
+            combo_sum: 0,
+            combo_hits: 0,
+            judged: 0,
             chain_miss_count: 0,
+            model,
             score: 0,
             accuracy: 1.,
             hp: 1.,
+            multiplier: mods::multiplier(&mods),
+            mods,
         }
     }
 
     pub fn register_judgement(&mut self, judgement: JudgementResult<J>) {
-        match judgement {
-            JudgementResult::Hit(_) => {
+        match &judgement {
+            JudgementResult::Hit(h) if h.affects_combo() => {
                 self.combo += 1;
                 self.top_combo = self.top_combo.max(self.combo);
 
-                self.internal_score += self.combo as f64 / self.max_combo as f64;
-                self.score = (self.internal_score * 1_000_000. * 2. / (self.max_combo as f64 + 1.))
-                    .round() as u32;
+                self.combo_sum += self.combo as u64;
+                self.combo_hits += 1;
+                self.judged += 1;
                 self.chain_miss_count = 0;
 
                 self.hp += (self.combo as f32 / self.max_combo as f32) * 0.1;
                 self.hp = self.hp.min(1.0);
             }
+            JudgementResult::Hit(_) => {}
             JudgementResult::Miss => {
                 self.combo = 0;
+                self.judged += 1;
 
                 #[allow(clippy::excessive_precision)]
                 let hp_drain = polynomial(
@@ -113,16 +369,41 @@ impl<J: Judgement> ScoreRecorder<J> {
 
         *self.judgements.get_mut(&judgement).unwrap() += 1;
         self.accuracy = accuracy(&self.judgements);
+        self.score = (self.model.raw_score(
+            self.combo_sum,
+            self.combo_hits,
+            self.judged,
+            self.max_combo,
+            self.accuracy,
+        ) * self.multiplier as f64)
+            .round() as u32;
     }
 
-    pub fn to_score(&self, diff_id: u32) -> Score<J> {
+    /// Drains HP over time, `hp_drain` being the beatmap's HP drain setting \[0; 10\].
+    pub fn drain(&mut self, seconds: f32, hp_drain: f32) {
+        // At HP 10, a full bar lasts 50 seconds without any catches.
+        self.hp -= seconds * hp_drain / 500.;
+        self.hp = self.hp.max(0.);
+    }
+
+    /// Whether the player ran out of HP. Never happens with [`Mod::NoFail`].
+    pub fn failed(&self) -> bool {
+        self.hp <= 0. && !self.mods.contains(&Mod::NoFail)
+    }
+
+    pub fn to_score(&self, diff_hash: DifficultyHash) -> Score<J> {
         Score {
             username: None,
-            diff_id,
+            diff_hash,
             top_combo: self.top_combo,
             score: self.score,
-            passed: self.hp > 0.5,
+            passed: !self.failed(),
             judgements: self.judgements.clone(),
+            mods: self.mods.clone(),
+            model: self.model,
+            grade: Grade::from_judgements(&self.judgements, &self.mods),
+            // The recorder doesn't know the chart's difficulty, it's filled in at the end of gameplay.
+            pp: 0.,
         }
     }
 }
@@ -132,9 +413,9 @@ fn test_score_recorder_limits() {
     use crate::rulesets::catch::CatchJudgement;
     for max_combo in 1..4000 {
         dbg!(max_combo);
-        let mut recorder = ScoreRecorder::new(max_combo);
+        let mut recorder = ScoreRecorder::new(max_combo, Vec::new(), ScoringModel::ComboWeighted);
         for _ in 0..max_combo {
-            recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Perfect));
+            recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Fruit));
         }
         assert_eq!(recorder.score, 1_000_000);
     }
@@ -143,30 +424,107 @@ fn test_score_recorder_limits() {
 #[test]
 fn test_hp() {
     use crate::rulesets::catch::CatchJudgement;
-    let mut recorder = ScoreRecorder::new(100);
+    let mut recorder = ScoreRecorder::new(100, Vec::new(), ScoringModel::ComboWeighted);
     assert_eq!(recorder.hp, 1.0);
     for _ in 0..10 {
-        recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Perfect));
+        recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Fruit));
     }
     assert_eq!(recorder.hp, 1.0);
     recorder.register_judgement(JudgementResult::Miss);
     assert_eq!(recorder.hp, 0.9749252);
     for _ in 0..10 {
-        recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Perfect));
+        recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Fruit));
     }
     assert_eq!(recorder.hp, 1.0);
     for _ in 0..3 {
         recorder.register_judgement(JudgementResult::Miss);
     }
     assert_eq!(recorder.hp, 0.8362208);
-    recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Perfect));
+    recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Fruit));
     for _ in 0..6 {
         recorder.register_judgement(JudgementResult::Miss);
     }
     assert_eq!(recorder.hp, 0.22481588);
-    recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Perfect));
+    recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Fruit));
     for _ in 0..12 {
         recorder.register_judgement(JudgementResult::Miss);
     }
     assert_eq!(recorder.hp, 0.0);
 }
+
+#[test]
+fn test_fail() {
+    use crate::rulesets::catch::CatchJudgement;
+    let mut recorder =
+        ScoreRecorder::<CatchJudgement>::new(100, Vec::new(), ScoringModel::ComboWeighted);
+    recorder.drain(10., 5.);
+    assert_eq!(recorder.hp, 0.9);
+    assert!(!recorder.failed());
+    recorder.drain(100., 5.);
+    assert_eq!(recorder.hp, 0.0);
+    assert!(recorder.failed());
+    assert!(!recorder.to_score(DifficultyHash::default()).passed);
+
+    let mut recorder =
+        ScoreRecorder::<CatchJudgement>::new(100, vec![Mod::NoFail], ScoringModel::ComboWeighted);
+    recorder.drain(100., 5.);
+    assert!(!recorder.failed());
+    assert!(recorder.to_score(DifficultyHash::default()).passed);
+}
+
+#[test]
+fn test_score_recorder_multiplier() {
+    use crate::rulesets::catch::CatchJudgement;
+    let mut recorder = ScoreRecorder::new(100, vec![Mod::NoFail], ScoringModel::ComboWeighted);
+    for _ in 0..100 {
+        recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Fruit));
+    }
+    assert_eq!(recorder.score, 500_000);
+}
+
+#[test]
+fn test_accuracy() {
+    use crate::rulesets::catch::CatchJudgement;
+    let mut recorder = ScoreRecorder::new(2, Vec::new(), ScoringModel::ComboWeighted);
+    recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Fruit));
+    recorder.register_judgement(JudgementResult::Hit(CatchJudgement::TinyDropletMiss));
+    assert_eq!(recorder.combo, 1);
+    assert_eq!(recorder.accuracy, 0.5);
+    recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Banana));
+    recorder.register_judgement(JudgementResult::Hit(CatchJudgement::BananaMiss));
+    assert_eq!(recorder.accuracy, 0.5);
+    recorder.register_judgement(JudgementResult::Miss);
+    assert_eq!(recorder.combo, 0);
+    assert_eq!(recorder.accuracy, 1. / 3.);
+}
+
+#[test]
+fn test_scoring_models() {
+    use crate::rulesets::catch::CatchJudgement;
+    for model in ScoringModel::ALL {
+        let mut recorder = ScoreRecorder::new(100, Vec::new(), model);
+        for _ in 0..100 {
+            recorder.register_judgement(JudgementResult::Hit(CatchJudgement::Fruit));
+        }
+        let expected = match model {
+            ScoringModel::ComboWeighted | ScoringModel::Standardised => 1_000_000,
+            ScoringModel::Classic => 89_400,
+        };
+        assert_eq!(recorder.score, expected);
+        assert_eq!(
+            model.estimate(&recorder.judgements, recorder.top_combo, &[]),
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_grade() {
+    assert_eq!(Grade::new(1.0, 0, &[]), Grade::SS);
+    assert_eq!(Grade::new(1.0, 0, &[Mod::Hidden]), Grade::SilverSS);
+    assert_eq!(Grade::new(0.99, 0, &[Mod::Flashlight]), Grade::SilverS);
+    assert_eq!(Grade::new(0.99, 1, &[]), Grade::A);
+    assert_eq!(Grade::new(0.92, 0, &[]), Grade::B);
+    assert_eq!(Grade::new(0.86, 0, &[]), Grade::C);
+    assert_eq!(Grade::new(0.5, 0, &[]), Grade::D);
+}
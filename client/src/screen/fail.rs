@@ -0,0 +1,140 @@
+use super::{
+    game::{GameMessage, SharedGameData},
+    gameplay::{Gameplay, Replay},
+    select::SelectScreen,
+    Screen,
+};
+use crate::{
+    draw_text_centered,
+    rulesets::catch::{CatchInput, CatchSyncFrame},
+    ui::{
+        menubutton::Popout,
+        menubuttonlist::{MenuButtonList, MenuButtonListMessage},
+        Message, MessageData, UiElement,
+    },
+};
+use async_trait::async_trait;
+use macroquad::prelude::*;
+
+/// Shown after running out of HP.
+pub struct FailScreen {
+    chart_name: String,
+    diff_name: String,
+    accuracy: f32,
+    /// How far into the map the player got \[0; 1\].
+    progress: f32,
+    replay: Replay<CatchInput, CatchSyncFrame>,
+
+    options: MenuButtonList,
+    rx: flume::Receiver<Message>,
+}
+
+impl FailScreen {
+    pub fn new(
+        chart_name: String,
+        diff_name: String,
+        accuracy: f32,
+        progress: f32,
+        replay: Replay<CatchInput, CatchSyncFrame>,
+    ) -> Self {
+        let (tx, rx) = flume::unbounded();
+        FailScreen {
+            chart_name,
+            diff_name,
+            accuracy,
+            progress: progress.clamp(0., 1.),
+            replay,
+            options: MenuButtonList::new(
+                "fail_options".to_string(),
+                Popout::Towards,
+                Rect::new(
+                    screen_width() / 2. - 400. / 2.,
+                    screen_height() / 2. - 105. * 3. / 2.,
+                    400.,
+                    400.,
+                ),
+                vec![
+                    vec!["Retry".into()],
+                    vec!["Quit".into()],
+                    vec!["View replay".into()],
+                ],
+                tx,
+            ),
+            rx,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for FailScreen {
+    async fn update(&mut self, data: SharedGameData) {
+        self.options.update(data.clone());
+        for message in self.rx.drain() {
+            self.options.handle_message(&message);
+            if message.target == self.options.id {
+                if let MessageData::MenuButtonList(MenuButtonListMessage::Selected(idx)) =
+                    message.data
+                {
+                    let chart_name = self.chart_name.clone();
+                    let diff_name = self.diff_name.clone();
+                    match idx {
+                        0 => data.broadcast(GameMessage::load_screen({
+                            let data = data.clone();
+                            async move { Gameplay::new(data, &chart_name, &diff_name).await }
+                        })),
+                        1 => data.broadcast(GameMessage::change_screen(
+                            SelectScreen::new(data.clone()).await,
+                        )),
+                        2 => data.broadcast(GameMessage::load_screen({
+                            let data = data.clone();
+                            let replay = self.replay.clone();
+                            async move {
+                                Gameplay::with_replay(data, &chart_name, &diff_name, replay).await
+                            }
+                        })),
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+
+        if is_key_pressed(KeyCode::Escape) {
+            data.broadcast(GameMessage::change_screen(
+                SelectScreen::new(data.clone()).await,
+            ));
+        }
+    }
+
+    fn draw(&self, data: SharedGameData) {
+        draw_texture_ex(
+            data.background(),
+            0.,
+            0.,
+            Color::new(0.5, 0.2, 0.2, 0.2),
+            DrawTextureParams {
+                dest_size: Some(vec2(screen_width(), screen_height())),
+                ..Default::default()
+            },
+        );
+
+        draw_text_centered(
+            &format!("{} [{}] (Failed)", self.chart_name, self.diff_name),
+            screen_width() / 2.,
+            screen_height() / 2. - 105. * 3. / 2. - 60.,
+            36,
+            RED,
+        );
+        draw_text_centered(
+            &format!(
+                "{:.2}% at {:.0}% of the map",
+                self.accuracy * 100.,
+                self.progress * 100.
+            ),
+            screen_width() / 2.,
+            screen_height() / 2. - 105. * 3. / 2. - 20.,
+            28,
+            WHITE,
+        );
+        self.options.draw(data);
+    }
+}
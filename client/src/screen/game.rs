@@ -9,13 +9,16 @@ use crate::{
     azusa::{Azusa, ClientPacket, ServerPacket},
     cache::Cache,
     chat,
-    config::{get_value, set_value, KeyBinds},
+    config::{get_value, set_value, Action, KeyBinds},
+    difficulty::DifficultyCache,
+    identity::DifficultyHash,
+    input::Gamepads,
     leaderboard::Leaderboard,
+    library::{self, ChartLibrary},
     promise::{Promise, PromiseExecutor},
     LogType,
 };
 use aether::log;
-use gluesql::{prelude::Glue, sled_storage::SledStorage};
 use kira::{
     manager::{AudioManager, AudioManagerSettings},
     sound::static_sound::{PlaybackState, StaticSoundData},
@@ -43,6 +46,8 @@ pub enum GameMessage {
         looping: bool,
     },
     SetMusicRate(f32),
+    /// Seek the music to a position in seconds.
+    SeekMusic(f32),
     PauseMusic,
     ResumeMusic,
     SetMainVolume(f32),
@@ -130,23 +135,25 @@ impl Game {
 
         let mut audio = AudioManager::new(AudioManagerSettings::default()).unwrap();
 
-        let leaderboard = Leaderboard::new().await;
+        // The game can't run without its databases.
+        let mut leaderboard = Leaderboard::new().await.unwrap_or_else(|e| {
+            log!(LogType::General, "Couldn't open the scores: {}", e);
+            panic!("Couldn't open the scores: {}", e)
+        });
 
         let audio_cache = Cache::new("data/cache/audio");
         let image_cache = Cache::new("data/cache/image");
 
         let first_time = get_value::<bool>("first_time").unwrap_or(true);
-        let binds = get_value::<KeyBinds>("binds").unwrap_or(KeyBinds {
-            right: KeyCode::D,
-            left: KeyCode::A,
-            dash: KeyCode::RightShift,
-        });
+        let binds = get_value::<KeyBinds>("binds").unwrap_or_default();
         let token = get_value::<uuid::Uuid>("token");
 
         let panning = get_value::<(f32, f32)>("panning").unwrap_or((0.25, 0.75));
         let main_volume = get_value("main_volume").unwrap_or(0.25);
         let hitsound_volume = get_value("hitsound_volume").unwrap_or(1.0);
         let max_stack = get_value("max_stack").unwrap_or(16);
+        let scoring_model = get_value("scoring_model").unwrap_or_default();
+        let input_mode = get_value("input_mode").unwrap_or_default();
         let playfield_size = get_value("playfield_size").unwrap_or(2. / 3.);
 
         // Linux usually needs a +30ms offset for compatibility with windows. (I think..)
@@ -169,20 +176,6 @@ impl Game {
             (track, volume)
         };
 
-        let files = load_file("resources/Kizuato/files.json").await.unwrap();
-        let files: Vec<String> = serde_json::from_slice(&files).unwrap();
-        files
-            .into_iter()
-            .for_each(|path| audio_cache.whitelist(format!("resources/Kizuato/{}", path)));
-
-        let sound = audio_cache
-            .get_sound("resources/Kizuato/audio.wav", main_track.id())
-            .await
-            .unwrap();
-
-        let mut instance = audio.play(sound).unwrap();
-        instance.set_volume(0., Tween::default()).unwrap();
-
         let combo_break = audio_cache
             .get_sound_bypass("resources/combobreak.wav", hitsound_track.id())
             .await
@@ -192,20 +185,60 @@ impl Game {
             .await
             .unwrap();
 
-        let fresh_cache = !std::path::PathBuf::from("data/.chart").exists();
-        let mut chart_db = Glue::new(SledStorage::new("data/.chart").unwrap());
-        if fresh_cache {
-            for stmt in include_str!("../queries/initialize_chart.sql")
-                .split(';')
-                .filter(|s| !s.is_empty())
-            {
-                chart_db.execute_async(stmt).await.unwrap();
+        let mut chart_library = ChartLibrary::new().await.unwrap_or_else(|e| {
+            log!(LogType::General, "Couldn't open the chart library: {}", e);
+            panic!("Couldn't open the chart library: {}", e)
+        });
+        if let Err(e) = chart_library.rescan().await {
+            log!(LogType::General, "Couldn't scan the chart library: {}", e);
+        }
+        if let Ok(charts) = chart_library.charts() {
+            if let Err(e) = leaderboard.link_legacy_scores(&charts).await {
+                log!(
+                    LogType::General,
+                    "Couldn't link old scores to their charts: {}",
+                    e
+                );
             }
         }
 
+        // Silent until a chart is selected, any sound works if the library is empty.
+        let sound = match chart_library
+            .charts()
+            .ok()
+            .and_then(|charts| charts.into_iter().next())
+        {
+            Some(chart) => match library::read_manifest(&chart.title).await {
+                Ok((files, audio_file)) => {
+                    files.into_iter().for_each(|path| {
+                        audio_cache.whitelist(library::chart_file(&chart.title, &path))
+                    });
+                    audio_cache
+                        .get_sound(
+                            &library::chart_file(&chart.title, &audio_file),
+                            main_track.id(),
+                        )
+                        .await
+                        .unwrap_or_else(|e| {
+                            log!(LogType::General, "Couldn't load the music: {:?}", e);
+                            hit_normal.clone()
+                        })
+                }
+                Err(e) => {
+                    log!(LogType::General, "Couldn't load the music: {}", e);
+                    hit_normal.clone()
+                }
+            },
+            None => hit_normal.clone(),
+        };
+
+        let mut instance = audio.play(sound).unwrap();
+        instance.set_volume(0., Tween::default()).unwrap();
+
         let data = Rc::new(GameData {
             audio_cache,
             image_cache,
+            difficulty_cache: DifficultyCache::new("data/cache/difficulty"),
             button: load_texture("resources/button.png").await.unwrap(),
             catcher: load_texture("resources/catcher.png").await.unwrap(),
             fruit: load_texture("resources/fruit.png").await.unwrap(),
@@ -222,7 +255,9 @@ impl Game {
                     title: "NULL".to_owned(),
                     difficulties: vec![DifficultyInfo {
                         id: 0,
+                        hash: DifficultyHash::default(),
                         name: "NULL".to_owned(),
+                        stamp: None,
                     }],
                 },
                 difficulty_idx: 0,
@@ -244,9 +279,12 @@ impl Game {
             main_track,
             playfield_size: Cell::new(playfield_size),
             max_stack: Cell::new(max_stack),
+            scoring_model: Cell::new(scoring_model),
+            input_mode: Cell::new(input_mode),
+            gamepads: RefCell::new(Gamepads::new()),
             mods: RefCell::new(Vec::new()),
             rate: Cell::new(1.0),
-            chart_db: RefCell::new(chart_db),
+            library: RefCell::new(chart_library),
         });
 
         let azusa = if let Some(token) = token {
@@ -327,8 +365,12 @@ impl Game {
             }
         }
 
+        let binds = self.data.state().binds.clone();
+        // Don't toggle overlays while the settings wait for a key to bind.
+        let listening = matches!(&self.overlay, Some(OverlayEnum::Settings(settings)) if settings.is_listening());
+
         if let Some(azusa) = &self.azusa {
-            if azusa.connected() && is_key_pressed(KeyCode::F9) {
+            if azusa.connected() && !listening && binds.is_pressed(Action::Chat) {
                 if let Some(OverlayEnum::Chat(_)) = self.overlay {
                     log!(LogType::General, "Closing chat overlay");
                     self.overlay = None;
@@ -339,7 +381,7 @@ impl Game {
             }
         }
 
-        if is_key_pressed(KeyCode::F1) {
+        if !listening && binds.is_pressed(Action::Settings) {
             if let Some(OverlayEnum::Settings(_)) = self.overlay {
                 log!(LogType::General, "Closing settings overlay");
                 self.overlay = None;
@@ -361,6 +403,19 @@ impl Game {
             }
         }
 
+        if let Some(azusa) = &self.azusa {
+            if azusa.connected() && !listening && binds.is_pressed(Action::Ranking) {
+                if let Some(OverlayEnum::Ranking(_)) = self.overlay {
+                    log!(LogType::General, "Closing ranking overlay");
+                    self.overlay = None;
+                } else {
+                    log!(LogType::General, "Opening ranking overlay");
+                    self.data.send_server(ClientPacket::RequestRanking);
+                    self.overlay = Some(OverlayEnum::Ranking(overlay::Ranking::new()));
+                }
+            }
+        }
+
         if is_key_pressed(KeyCode::F7) && self.azusa.is_none() {
             if let Some(OverlayEnum::Login(_)) = self.overlay {
                 log!(LogType::General, "Closing login overlay");
@@ -459,6 +514,14 @@ impl Game {
                         self.data.promises().cancel(&old_loading_promise);
                     }
                 }
+                GameMessage::SeekMusic(position) => {
+                    self.data
+                        .state_mut()
+                        .music
+                        .seek_to(position as f64)
+                        .unwrap();
+                    self.data.predicted_time.set(position);
+                }
                 GameMessage::SetMusicRate(rate) => {
                     self.data.rate.set(rate);
                     self.data
@@ -525,6 +588,11 @@ impl Game {
                         self.sent_ping = false;
                     }
                     ServerPacket::Chat(packet) => self.data.state_mut().chat.handle_packet(packet),
+                    ServerPacket::Ranking(entries) => {
+                        if let Some(OverlayEnum::Ranking(ranking)) = &mut self.overlay {
+                            ranking.set_entries(entries);
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -1,28 +1,60 @@
 use super::{
+    fail::FailScreen,
     game::{GameMessage, SharedGameData},
     result::ResultScreen,
     select::SelectScreen,
     Screen,
 };
 use crate::{
+    analytics::HitEvent,
     azusa::ClientPacket,
-    chart::{Chart, EventData, HitSoundKind},
+    chart::{Chart, EventData, FruitKind, HitSoundKind},
+    config::{key_name, Action},
     convert::ConvertFrom,
     draw_text_centered,
     frozen::Frozen,
-    math,
+    identity::DifficultyHash,
+    input::{self, InputMode},
+    library, math,
+    mods::{self, Mod},
+    performance::{self, PerformanceInput},
+    practice::{LoopStats, Section},
+    replays,
     rulesets::{
-        catch::{catcher_speed, CatchInput, CatchRuleset},
+        catch::{catch_offset, catcher_speed, CatchInput, CatchRuleset, CatchSyncFrame},
         JudgementResult, Ruleset,
     },
     score::ScoreRecorder,
+    ui::{
+        menubutton::{MenuButton, MenuButtonMessage, Popout},
+        menubuttonlist::{MenuButtonList, MenuButtonListMessage},
+        Message, MessageData, UiElement,
+    },
+    LogType,
 };
+use aether::log;
 use async_trait::async_trait;
 use instant::SystemTime;
-use kira::tween::Tween;
+use kira::{
+    dsp::Frame,
+    sound::static_sound::{StaticSoundData, StaticSoundSettings},
+    track::TrackId,
+    tween::Tween,
+};
 use macroquad::{prelude::*, rand::rand};
 use num_format::{Locale, ToFormattedString};
 
+/// Stands in for music that couldn't be loaded, so the chart can still be played to the end.
+fn silence(chart: &Chart, track: TrackId) -> StaticSoundData {
+    const SAMPLE_RATE: u32 = 1000;
+    let length = chart.fruits.last().map_or(0., |fruit| fruit.time) + 2.;
+    StaticSoundData {
+        sample_rate: SAMPLE_RATE,
+        frames: vec![Frame::ZERO; (length * SAMPLE_RATE as f32) as usize].into(),
+        settings: StaticSoundSettings::default().track(track),
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ReplaySyncFrame<F> {
     pub time: f32,
@@ -54,12 +86,30 @@ mod system_time_serde {
     }
 }
 
+/// A jump in the music during a recorded play, from resuming after a pause or skipping the intro.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct ReplaySeek {
+    /// Number of inputs recorded before seeking.
+    pub input_index: u32,
+    /// Music position that was seeked to.
+    pub time: f32,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Replay<I, S> {
     #[serde(with = "system_time_serde")]
     pub start: SystemTime,
     pub inputs: Vec<I>,
     pub sync_frames: Vec<ReplaySyncFrame<S>>,
+    /// Replays saved before seeks were recorded don't have this.
+    #[serde(default)]
+    pub seeks: Vec<ReplaySeek>,
+    /// Where the inputs came from.
+    #[serde(default)]
+    pub input_mode: InputMode,
+    /// Difficulty that was played. Replays saved before difficulties were hashed don't have this.
+    #[serde(default)]
+    pub diff_hash: Option<DifficultyHash>,
 }
 
 impl<I, S> Replay<I, S> {
@@ -68,6 +118,9 @@ impl<I, S> Replay<I, S> {
             start: SystemTime::now(),
             inputs: Vec::with_capacity(predicted_frame_count),
             sync_frames: Vec::with_capacity(predicted_frame_count / 3),
+            seeks: Vec::new(),
+            input_mode: InputMode::default(),
+            diff_hash: None,
         }
     }
 }
@@ -78,9 +131,40 @@ pub enum ReplayType {
     Playback {
         input_index: usize,
         sync_frame_index: usize,
+        seek_index: usize,
     },
 }
 
+/// Time in seconds it takes the music to slow down to a stop after failing.
+const FAIL_DURATION: f32 = 2.;
+
+/// Time in seconds the music is rewound when resuming from a pause.
+const RESUME_REWIND: f32 = 1.;
+
+/// Time in seconds counted down before the music resumes after a pause.
+const RESUME_COUNTDOWN: f32 = 3.;
+
+/// Time in seconds the retry key has to be held.
+const RETRY_HOLD: f32 = 0.5;
+
+/// Time in seconds before the first fruit starts falling that skipping the intro seeks to.
+const SKIP_LEAD_IN: f32 = 1.;
+
+/// Skipping is only offered if it would skip more than this many seconds.
+const SKIP_THRESHOLD: f32 = 2.;
+
+/// Time in seconds between two objects after which HP stops draining.
+const BREAK_THRESHOLD: f32 = 2.;
+
+/// Time in seconds after the last fruit of a practice section before it starts over.
+const LOOP_DELAY: f32 = 1.;
+
+/// Time in seconds before the requested moment that replays opened at a moment start from.
+const REPLAY_LEAD_IN: f32 = 3.;
+
+/// Number of past loops listed while practicing.
+const SHOWN_LOOPS: usize = 10;
+
 pub struct DisposedFruit {
     gravity: f32,
     x_speed: f32,
@@ -93,13 +177,19 @@ pub struct DisposedFruits {
     time_since_dispose: f32,
 }
 
-pub enum Mod {
-    Rate(f32),
+/// State of practice mode, where a section of the chart is looped.
+struct Practice {
+    section: Section,
+    loops: Vec<LoopStats>,
 }
 
 pub struct Gameplay<R: Ruleset> {
     chart_name: String,
+    diff_name: String,
+    diff_hash: DifficultyHash,
     recorder: ScoreRecorder<R::Judgement>,
+    /// Every judged object in order, shown on the result screen.
+    hit_events: Vec<HitEvent<R::Judgement>>,
     replay: Replay<R::Input, R::SyncFrame>,
     replay_type: ReplayType,
     ruleset: R,
@@ -112,6 +202,8 @@ pub struct Gameplay<R: Ruleset> {
     use_predicted_time: bool,
 
     chart: Frozen<Chart>,
+    sound: StaticSoundData,
+    star_rating: f32,
     queued_fruits: Vec<usize>,
     plate: Vec<(f32, Color)>,
     disposed_fruits: Vec<DisposedFruits>,
@@ -119,8 +211,19 @@ pub struct Gameplay<R: Ruleset> {
     fade_out: f32,
     started: bool,
     ended: bool,
+    /// Time since the player ran out of HP, if they did.
+    time_since_fail: Option<f32>,
 
     paused: bool,
+    pause_menu: Option<MenuButtonList>,
+    skip_button: MenuButton,
+    ui_tx: flume::Sender<Message>,
+    ui_rx: flume::Receiver<Message>,
+    /// Time the retry key has been held for.
+    retry_held: f32,
+    /// Time left until the music resumes after continuing from the pause menu.
+    resume_countdown: Option<f32>,
+    practice: Option<Practice>,
 
     event_idx: usize,
     hitsound: HitSoundKind,
@@ -130,29 +233,63 @@ pub struct Gameplay<R: Ruleset> {
 
 impl Gameplay<CatchRuleset> {
     pub async fn new(data: SharedGameData, chart_name: &str, diff: &str) -> Self {
-        let beatmap_data = load_file(&format!("resources/{}/{}.osu", chart_name, diff))
-            .await
-            .unwrap();
+        let beatmap_data =
+            library::load_file(&library::chart_file(chart_name, &format!("{}.osu", diff)))
+                .await
+                .unwrap();
         let beatmap_content = std::str::from_utf8(&beatmap_data).unwrap();
         let beatmap =
             osu_parser::load_content(beatmap_content, osu_parser::BeatmapParseOptions::default())
                 .unwrap();
         let chart = Chart::convert_from(&beatmap);
+        let diff_hash = DifficultyHash::new(&chart, Some(&beatmap_data));
+        let star_rating = data
+            .difficulty_cache
+            .get_or_calculate(&diff_hash, &data.mods.borrow(), || chart.clone())
+            .star_rating;
+
+        let sound = match library::read_manifest(chart_name).await {
+            Ok((_, audio_file)) => data
+                .audio_cache
+                .get_sound(
+                    &library::chart_file(chart_name, &audio_file),
+                    data.main_track.id(),
+                )
+                .await
+                .map_err(|e| format!("{:?}", e)),
+            Err(e) => Err(e.to_string()),
+        };
+        let sound = sound.unwrap_or_else(|e| {
+            log!(
+                LogType::General,
+                "Couldn't load the music of {}: {}",
+                chart_name,
+                e
+            );
+            silence(&chart, data.main_track.id())
+        });
 
-        let sound = data
-            .audio_cache
-            .get_sound(
-                &format!("resources/{}/audio.wav", chart_name),
-                data.main_track.id(),
-            )
-            .await
-            .unwrap();
+        let gameplay =
+            Gameplay::from_chart(data, chart_name, diff, diff_hash, chart, sound, star_rating);
+        next_frame().await;
+        gameplay
+    }
 
+    /// Creates gameplay from an already loaded chart and its music.
+    fn from_chart(
+        data: SharedGameData,
+        chart_name: &str,
+        diff: &str,
+        diff_hash: DifficultyHash,
+        chart: Chart,
+        sound: StaticSoundData,
+        star_rating: f32,
+    ) -> Self {
         // Time from the last fruit to the end of the music.
         let music_length = sound.duration().as_secs_f32();
         let time_to_end = music_length - chart.fruits.last().unwrap().time;
 
-        data.broadcast(GameMessage::update_music(sound));
+        data.broadcast(GameMessage::update_music(sound.clone()));
         data.broadcast(GameMessage::PauseMusic);
 
         let first_fruit = chart.fruits.first().unwrap();
@@ -162,15 +299,19 @@ impl Gameplay<CatchRuleset> {
         } else {
             0.
         };
-        next_frame().await;
 
         // Assume 60 frames per second.
         let approx_frame_count = (music_length * 60.) as usize;
 
-        let replay = Replay::new(approx_frame_count);
+        let mut replay = Replay::new(approx_frame_count);
+        replay.input_mode = data.input_mode.get();
+        replay.diff_hash = Some(diff_hash.clone());
 
+        let (ui_tx, ui_rx) = flume::unbounded();
         let mut gameplay = Gameplay {
             chart_name: chart_name.to_owned(),
+            diff_name: diff.to_owned(),
+            diff_hash,
             ruleset: CatchRuleset::new(),
 
             replay,
@@ -179,16 +320,47 @@ impl Gameplay<CatchRuleset> {
             time: -time_countdown,
             predicted_time: -time_countdown,
             prev_time: -time_countdown,
-            recorder: ScoreRecorder::new(chart.fruits.len() as u32),
+            recorder: ScoreRecorder::new(
+                chart.max_combo(),
+                data.mods.borrow().clone(),
+                data.scoring_model.get(),
+            ),
+            hit_events: Vec::new(),
             queued_fruits: (0..chart.fruits.len()).collect(),
+            star_rating,
             chart: Frozen(chart),
+            sound,
             show_debug_hitbox: false,
             use_predicted_time: true,
             time_countdown,
             started: false,
             fade_out: time_to_end.max(1.).min(3.),
             ended: false,
+            time_since_fail: None,
             paused: false,
+            pause_menu: None,
+            skip_button: MenuButton::new(
+                "skip".to_string(),
+                vec![format!(
+                    "Skip ({})",
+                    data.state()
+                        .binds
+                        .keys(Action::Skip)
+                        .iter()
+                        .map(|&key| key_name(key))
+                        .collect::<Vec<_>>()
+                        .join(" / ")
+                )],
+                Popout::None,
+                Rect::new(screen_width() - 305., screen_height() - 105., 300., 100.),
+                ui_tx.clone(),
+                false,
+            ),
+            ui_tx,
+            ui_rx,
+            retry_held: 0.,
+            resume_countdown: None,
+            practice: None,
 
             event_idx: 0,
             hitsound: HitSoundKind::Normal,
@@ -205,6 +377,313 @@ impl Gameplay<CatchRuleset> {
         gameplay
     }
 
+    /// Plays back `replay` instead of reading input. Scores aren't submitted.
+    pub async fn with_replay(
+        data: SharedGameData,
+        chart_name: &str,
+        diff: &str,
+        replay: Replay<CatchInput, CatchSyncFrame>,
+    ) -> Self {
+        let mut gameplay = Gameplay::new(data, chart_name, diff).await;
+        if matches!(&replay.diff_hash, Some(diff_hash) if *diff_hash != gameplay.diff_hash) {
+            log!(
+                LogType::General,
+                "Replay was recorded on a different version of {} [{}]",
+                chart_name,
+                diff
+            );
+        }
+        gameplay.replay = replay;
+        gameplay.replay_type = ReplayType::Playback {
+            input_index: 0,
+            sync_frame_index: 0,
+            seek_index: 0,
+        };
+        gameplay
+    }
+
+    /// Plays back `replay` starting shortly before `time`, skipping everything before it.
+    pub async fn with_replay_at(
+        data: SharedGameData,
+        chart_name: &str,
+        diff: &str,
+        replay: Replay<CatchInput, CatchSyncFrame>,
+        time: f32,
+    ) -> Self {
+        let mut gameplay = Gameplay::with_replay(data.clone(), chart_name, diff, replay).await;
+        let start = (time - REPLAY_LEAD_IN).max(0.);
+
+        // Continue from the last sync frame before the start.
+        if let Some((idx, sync_frame)) = gameplay
+            .replay
+            .sync_frames
+            .iter()
+            .enumerate()
+            .filter(|(_, sync_frame)| sync_frame.time <= start)
+            .last()
+        {
+            gameplay.ruleset.handle_sync_frame(&sync_frame.data);
+            gameplay.replay_type = ReplayType::Playback {
+                input_index: sync_frame.input_index as usize,
+                sync_frame_index: idx + 1,
+                seek_index: gameplay
+                    .replay
+                    .seeks
+                    .iter()
+                    .filter(|seek| seek.input_index <= sync_frame.input_index)
+                    .count(),
+            };
+        }
+
+        let chart = &gameplay.chart;
+        gameplay
+            .queued_fruits
+            .retain(|&idx| chart.fruits[idx].time >= start);
+        gameplay.time_countdown = 0.;
+        data.broadcast(GameMessage::SeekMusic(start));
+        gameplay
+    }
+
+    /// Loops `section` of the chart. Scores aren't submitted, loops are saved separately instead.
+    pub async fn practice(
+        data: SharedGameData,
+        chart_name: &str,
+        diff: &str,
+        section: Section,
+    ) -> Self {
+        let mut gameplay = Gameplay::new(data.clone(), chart_name, diff).await;
+        gameplay.start_practice(section, data);
+        gameplay
+    }
+
+    fn start_practice(&mut self, section: Section, data: SharedGameData) {
+        self.practice = Some(Practice {
+            section,
+            loops: Vec::new(),
+        });
+        // The music starts right before the section, so there's nothing to count down.
+        self.time_countdown = 0.;
+        self.start_loop(data);
+    }
+
+    /// Queues the fruits of the practice section again and seeks back to its start.
+    fn start_loop(&mut self, data: SharedGameData) {
+        let section = match &self.practice {
+            Some(practice) => practice.section,
+            None => return,
+        };
+        self.queued_fruits = section.fruits(&self.chart);
+        let max_combo = self
+            .queued_fruits
+            .iter()
+            .filter(|&&idx| self.chart.fruits[idx].kind.affects_combo())
+            .count() as u32;
+        self.recorder =
+            ScoreRecorder::new(max_combo, self.recorder.mods.clone(), self.recorder.model);
+        self.plate.clear();
+        self.hit_events.clear();
+        self.event_idx = 0;
+        self.fade_out = LOOP_DELAY;
+        data.broadcast(GameMessage::SeekMusic(section.lead_in(&self.chart)));
+    }
+
+    /// Starts the next loop once the section is cleared or failed.
+    fn update_practice(&mut self, data: SharedGameData) {
+        if self.paused {
+            return;
+        }
+        if self.queued_fruits.is_empty() {
+            self.fade_out -= get_frame_time();
+        }
+
+        let failed = self.recorder.failed();
+        if failed || self.fade_out <= 0. {
+            let stats = LoopStats {
+                accuracy: self.recorder.accuracy,
+                misses: self
+                    .recorder
+                    .judgements
+                    .get(&JudgementResult::Miss)
+                    .copied()
+                    .unwrap_or(0),
+                top_combo: self.recorder.top_combo,
+                max_combo: self.recorder.max_combo,
+                failed,
+            };
+            if let Some(practice) = &mut self.practice {
+                practice.loops.push(stats);
+            }
+            self.start_loop(data);
+        }
+    }
+
+    /// Saves the loops played so far, if practicing.
+    async fn save_practice(&self, data: SharedGameData) {
+        if let Some(practice) = &self.practice {
+            if let Err(e) = data
+                .state_mut()
+                .leaderboard
+                .submit_practice(&self.diff_hash, practice.section, &practice.loops)
+                .await
+            {
+                log!(LogType::General, "Couldn't save practice: {}", e);
+            }
+        }
+    }
+
+    fn pause(&mut self, data: SharedGameData) {
+        self.paused = true;
+        self.resume_countdown = None;
+        data.broadcast(GameMessage::PauseMusic);
+        self.pause_menu = Some(MenuButtonList::new(
+            "pause_menu".to_string(),
+            Popout::Towards,
+            Rect::new(
+                screen_width() / 2. - 400. / 2.,
+                screen_height() / 2. - 105. * 3. / 2.,
+                400.,
+                400.,
+            ),
+            vec![
+                vec!["Continue".into()],
+                vec!["Retry".into()],
+                vec!["Quit".into()],
+            ],
+            self.ui_tx.clone(),
+        ));
+    }
+
+    /// Rewinds the music a bit and starts counting down to resume.
+    fn resume(&mut self, data: SharedGameData) {
+        self.pause_menu = None;
+        self.resume_countdown = Some(RESUME_COUNTDOWN);
+
+        self.seek((data.time.get() - RESUME_REWIND).max(0.), data);
+    }
+
+    /// Seeks the music to `time`, recording it in the replay.
+    fn seek(&mut self, time: f32, data: SharedGameData) {
+        data.broadcast(GameMessage::SeekMusic(time));
+        if self.replay_type == ReplayType::Record {
+            self.replay.seeks.push(ReplaySeek {
+                input_index: self.replay.inputs.len() as u32,
+                time,
+            });
+        }
+    }
+
+    /// Music position the intro can be skipped to, if the first fruit is far enough from the start.
+    fn skip_target(&self) -> Option<f32> {
+        let target = self.chart.fruits[0].time - self.chart.fall_time - SKIP_LEAD_IN;
+        (self.practice.is_none()
+            && self.started
+            && !self.paused
+            && self.time < target - SKIP_THRESHOLD)
+            .then_some(target)
+    }
+
+    /// Starts the same chart over without loading anything again. Practice starts over from the first loop.
+    fn restart(&self, data: SharedGameData) -> Self {
+        let mut gameplay = Gameplay::from_chart(
+            data.clone(),
+            &self.chart_name,
+            &self.diff_name,
+            self.diff_hash.clone(),
+            (*self.chart).clone(),
+            self.sound.clone(),
+            self.star_rating,
+        );
+        if let Some(practice) = &self.practice {
+            gameplay.start_practice(practice.section, data);
+        }
+        gameplay
+    }
+
+    async fn handle_ui_messages(&mut self, data: SharedGameData) {
+        for message in self.ui_rx.drain().collect::<Vec<_>>() {
+            self.skip_button.handle_message(&message);
+            if message.target == self.skip_button.id {
+                if let MessageData::MenuButton(MenuButtonMessage::Selected) = message.data {
+                    if let Some(target) = self.skip_target() {
+                        self.seek(target, data.clone());
+                    }
+                }
+            }
+
+            let pause_menu = match &mut self.pause_menu {
+                Some(pause_menu) => pause_menu,
+                None => continue,
+            };
+            pause_menu.handle_message(&message);
+            if message.target == pause_menu.id {
+                if let MessageData::MenuButtonList(MenuButtonListMessage::Selected(idx)) =
+                    message.data
+                {
+                    match idx {
+                        0 => self.resume(data.clone()),
+                        1 => {
+                            self.save_practice(data.clone()).await;
+                            data.broadcast(GameMessage::change_screen(self.restart(data.clone())));
+                            self.ended = true;
+                        }
+                        2 => {
+                            self.save_practice(data.clone()).await;
+                            data.broadcast(GameMessage::change_screen(
+                                SelectScreen::new(data.clone()).await,
+                            ));
+                            self.ended = true;
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_pause(&mut self, data: SharedGameData) {
+        if let Some(pause_menu) = &mut self.pause_menu {
+            pause_menu.update(data.clone());
+        }
+
+        if let Some(countdown) = &mut self.resume_countdown {
+            *countdown -= get_frame_time();
+            if *countdown <= 0. {
+                self.resume_countdown = None;
+                self.paused = false;
+                data.broadcast(GameMessage::ResumeMusic);
+            }
+        }
+
+        if data.state().binds.is_pressed(Action::Pause) {
+            if self.pause_menu.is_some() {
+                self.resume(data);
+            } else {
+                self.pause(data);
+            }
+        }
+    }
+
+    /// Slows the music down to a stop, then shows the fail screen.
+    fn update_fail(&mut self, time_since_fail: f32, data: SharedGameData) {
+        let rate = mods::rate(&self.recorder.mods);
+        if time_since_fail < FAIL_DURATION {
+            let slowdown = 1. - time_since_fail / FAIL_DURATION;
+            data.broadcast(GameMessage::SetMusicRate((rate * slowdown).max(0.05)));
+            self.time_since_fail = Some(time_since_fail + get_frame_time());
+        } else if !self.ended {
+            data.broadcast(GameMessage::PauseMusic);
+            data.broadcast(GameMessage::SetMusicRate(rate));
+            data.broadcast(GameMessage::change_screen(FailScreen::new(
+                self.chart_name.clone(),
+                self.diff_name.clone(),
+                self.recorder.accuracy,
+                self.time / self.chart.fruits.last().unwrap().time,
+                self.replay.clone(),
+            )));
+            self.ended = true;
+        }
+    }
+
     fn catcher_y(&self) -> f32 {
         screen_height() - 148.
     }
@@ -221,6 +700,10 @@ impl Gameplay<CatchRuleset> {
         playfield_x + x * self.scale(data)
     }
 
+    fn screen_to_playfield_x(&self, x: f32, data: SharedGameData) -> f32 {
+        (x - self.playfield_to_screen_x(0., data.clone())) / self.scale(data)
+    }
+
     fn scale(&self, data: SharedGameData) -> f32 {
         let scale = screen_width() / self.playfield_width();
         scale * data.playfield_size.get()
@@ -255,11 +738,71 @@ impl Gameplay<CatchRuleset> {
         });
     }
 
+    /// Darkens everything except a window around the catcher.
+    fn draw_flashlight(&self, data: SharedGameData) {
+        const DARKNESS: Color = Color::new(0., 0., 0., 0.95);
+        let catcher_x = self.playfield_to_screen_x(self.ruleset.position, data.clone());
+        let half_width = self.chart.catcher_width * self.scale(data) * 1.5;
+        let top = self.catcher_y() - screen_height() / 3.;
+
+        draw_rectangle(0., 0., screen_width(), top, DARKNESS);
+        draw_rectangle(
+            0.,
+            top,
+            (catcher_x - half_width).max(0.),
+            screen_height() - top,
+            DARKNESS,
+        );
+        draw_rectangle(
+            catcher_x + half_width,
+            top,
+            screen_width() - (catcher_x + half_width),
+            screen_height() - top,
+            DARKNESS,
+        );
+    }
+
+    /// Lists the section being practiced and the most recent loops.
+    fn draw_practice(&self, practice: &Practice) {
+        draw_text(
+            &format!(
+                "Practice {:.1}s - {:.1}s, loop {}",
+                practice.section.start,
+                practice.section.end,
+                practice.loops.len() + 1
+            ),
+            5.,
+            60.,
+            24.,
+            WHITE,
+        );
+        let shown = practice.loops.len().saturating_sub(SHOWN_LOOPS);
+        for (idx, stats) in practice.loops.iter().enumerate().skip(shown) {
+            draw_text(
+                &format!(
+                    "#{} {:.2}% {} misses {}/{}x{}",
+                    idx + 1,
+                    stats.accuracy * 100.,
+                    stats.misses,
+                    stats.top_combo,
+                    stats.max_combo,
+                    if stats.failed { " (failed)" } else { "" }
+                ),
+                5.,
+                84. + 20. * (idx - shown) as f32,
+                20.,
+                if stats.failed { RED } else { WHITE },
+            );
+        }
+    }
+
     pub fn apply(&mut self, new_mod: &Mod, data: SharedGameData) {
         match new_mod {
             Mod::Rate(rate) => {
                 data.broadcast(GameMessage::SetMusicRate(*rate));
             }
+            // Only affect scoring and drawing, see `ScoreRecorder` and `draw`.
+            Mod::NoFail | Mod::Hidden | Mod::Flashlight => {}
         }
     }
 
@@ -268,6 +811,7 @@ impl Gameplay<CatchRuleset> {
             Mod::Rate(_rate) => {
                 data.broadcast(GameMessage::SetMusicRate(1.0));
             }
+            Mod::NoFail | Mod::Hidden | Mod::Flashlight => {}
         }
     }
 }
@@ -275,7 +819,7 @@ impl Gameplay<CatchRuleset> {
 #[async_trait(?Send)]
 impl Screen for Gameplay<CatchRuleset> {
     async fn update(&mut self, data: SharedGameData) {
-        let binds = data.state().binds;
+        let binds = data.state().binds.clone();
 
         if !self.started {
             // Manages the preempt timing before actually starting the map.
@@ -294,6 +838,11 @@ impl Screen for Gameplay<CatchRuleset> {
             self.predicted_time = data.predicted_time_with_offset();
         }
 
+        if let Some(time_since_fail) = self.time_since_fail {
+            self.update_fail(time_since_fail, data);
+            return;
+        }
+
         let mut should_dispose_plate = false;
 
         if !self.paused {
@@ -327,7 +876,7 @@ impl Screen for Gameplay<CatchRuleset> {
                 {
                     match &result {
                         JudgementResult::Hit((_judgement, details)) => {
-                            if !fruit.small {
+                            if fruit.kind == FruitKind::Fruit {
                                 let panning = math::remap(
                                     0.,
                                     self.playfield_width(),
@@ -342,8 +891,10 @@ impl Screen for Gameplay<CatchRuleset> {
                                     crate::chart::HitSoundKind::Drum => "Drum",
                                     crate::chart::HitSoundKind::Custom(s) => s,
                                 };
-                                let base_hs_path =
-                                    format!("resources/{}/HitSounds/{}", self.chart_name, hs_type);
+                                let base_hs_path = library::chart_file(
+                                    &self.chart_name,
+                                    &format!("HitSounds/{}", hs_type),
+                                );
 
                                 // If only you could move some values and borrow others..
                                 // TODO hitsounds should be pre-loaded before map even begins so we don't have to do this.
@@ -404,25 +955,47 @@ impl Screen for Gameplay<CatchRuleset> {
                         }
                     }
                     defer_delete.push(idx);
-                    self.recorder.register_judgement(result.map_hit(|(j, _)| j));
+                    let offset = catch_offset(&result);
+                    let judgement = result.map_hit(|(j, _)| j);
+                    self.recorder.register_judgement(judgement.clone());
+                    self.hit_events.push(HitEvent {
+                        time: fruit.time,
+                        judgement,
+                        offset,
+                        hp: self.recorder.hp,
+                        combo: self.recorder.combo,
+                        accuracy: self.recorder.accuracy,
+                    });
                 }
             }
 
+            // HP drains while playing, but not during the intro and breaks.
+            let in_break = self.queued_fruits.first().map_or(true, |&idx| {
+                self.chart.fruits[idx].time - self.time > BREAK_THRESHOLD
+            });
+            if self.time >= self.chart.fruits[0].time && !in_break {
+                self.recorder
+                    .drain((self.time - self.prev_time).max(0.), self.chart.hp_drain);
+            }
+            // Practice starts the loop over instead, see `update_practice`.
+            if self.recorder.failed() && self.practice.is_none() {
+                self.time_since_fail = Some(0.);
+            }
+
             let input = if let ReplayType::Playback { input_index, .. } = self.replay_type {
                 self.replay
                     .inputs
                     .get(input_index)
                     .copied()
-                    .unwrap_or(CatchInput {
-                        left: false,
-                        right: false,
-                        dash: false,
-                    })
+                    .unwrap_or_default()
             } else {
-                CatchInput {
-                    left: is_key_down(binds.left),
-                    right: is_key_down(binds.right),
-                    dash: is_key_down(binds.dash),
+                match self.replay.input_mode {
+                    InputMode::Keyboard => input::keyboard(&binds),
+                    InputMode::Gamepad => data.gamepads.borrow_mut().read(),
+                    InputMode::Mouse => {
+                        input::mouse(self.screen_to_playfield_x(mouse_position().0, data.clone()))
+                    }
+                    InputMode::Touch => input::touch(),
                 }
             };
 
@@ -449,8 +1022,16 @@ impl Screen for Gameplay<CatchRuleset> {
                 ReplayType::Playback {
                     input_index,
                     sync_frame_index,
+                    seek_index,
                 } => {
                     *input_index += 1;
+                    // Seek at the same point the player resumed from a pause or skipped.
+                    if let Some(seek) = self.replay.seeks.get(*seek_index) {
+                        if *input_index >= seek.input_index as usize {
+                            data.broadcast(GameMessage::SeekMusic(seek.time));
+                            *seek_index += 1;
+                        }
+                    }
                     if let Some(next_sync_frame) = self.replay.sync_frames.get(*sync_frame_index) {
                         if self.time >= next_sync_frame.time {
                             *input_index = next_sync_frame.input_index as usize;
@@ -474,26 +1055,66 @@ impl Screen for Gameplay<CatchRuleset> {
             self.dispose_plate();
         }
 
-        if self.queued_fruits.is_empty() && !self.ended {
+        if self.practice.is_some() {
+            self.update_practice(data.clone());
+        } else if self.queued_fruits.is_empty() && !self.ended {
             self.fade_out -= get_frame_time();
 
             // Once the screen has faded out, submit the score and change to the result screen.
             if self.fade_out <= 0. {
-                let diff_id = data.state().difficulty().id;
-                let score = self.recorder.to_score(diff_id);
-                if score.passed {
-                    data.state_mut().leaderboard.submit_score(&score).await;
-                }
-
-                data.send_server(ClientPacket::Submit(score.clone()));
-
+                let mut score = self.recorder.to_score(self.diff_hash.clone());
+                score.pp = performance::calculate(&PerformanceInput {
+                    star_rating: self.star_rating,
+                    fall_time: self.chart.fall_time,
+                    accuracy: self.recorder.accuracy,
+                    top_combo: score.top_combo,
+                    max_combo: self.recorder.max_combo,
+                    misses: score
+                        .judgements
+                        .get(&JudgementResult::Miss)
+                        .copied()
+                        .unwrap_or(0),
+                    mods: &score.mods,
+                });
                 let map_title = data.state().chart.title.clone();
                 let diff_title = data.state().difficulty().name.clone();
 
+                let mut replay_name = None;
+                if self.replay_type == ReplayType::Record {
+                    if score.passed {
+                        // Local scores keep their replay.
+                        replay_name = match replays::store(
+                            &replays::replay_name(&map_title, &diff_title, self.replay.start),
+                            &replays::encode(&self.replay),
+                        )
+                        .await
+                        {
+                            Ok(name) => Some(name),
+                            Err(e) => {
+                                log!(LogType::General, "Couldn't save replay: {}", e);
+                                None
+                            }
+                        };
+                        let length = self.time / mods::rate(&score.mods);
+                        if let Err(e) = data
+                            .state_mut()
+                            .leaderboard
+                            .submit_score(&score, length, replay_name.as_deref())
+                            .await
+                        {
+                            log!(LogType::General, "Couldn't save score: {}", e);
+                        }
+                    }
+
+                    data.send_server(ClientPacket::Submit(score.clone()));
+                }
+
                 data.broadcast(GameMessage::change_screen(
                     ResultScreen::<CatchRuleset>::new(
                         score,
                         self.replay.clone(),
+                        replay_name,
+                        std::mem::take(&mut self.hit_events),
                         map_title,
                         diff_title,
                     ),
@@ -509,19 +1130,35 @@ impl Screen for Gameplay<CatchRuleset> {
         if is_key_pressed(KeyCode::P) {
             self.use_predicted_time = !self.use_predicted_time;
         }
-        if is_key_pressed(KeyCode::End) {
-            data.broadcast(GameMessage::change_screen(
-                SelectScreen::new(data.clone()).await,
-            ));
+        if self.skip_target().is_some() {
+            self.skip_button.update(data.clone());
+            if binds.is_pressed(Action::Skip) {
+                self.ui_tx
+                    .send(Message {
+                        target: self.skip_button.id.clone(),
+                        data: MessageData::MenuButton(MenuButtonMessage::Selected),
+                    })
+                    .unwrap();
+            }
         }
-        if is_key_pressed(KeyCode::Escape) {
-            self.paused = !self.paused;
 
-            if self.paused {
-                data.broadcast(GameMessage::PauseMusic);
-            } else {
-                data.broadcast(GameMessage::ResumeMusic);
+        if !self.paused && !self.ended && binds.is_down(Action::Retry) {
+            self.retry_held += get_frame_time();
+            if self.retry_held >= RETRY_HOLD {
+                self.save_practice(data.clone()).await;
+                data.broadcast(GameMessage::change_screen(self.restart(data.clone())));
+                self.ended = true;
             }
+        } else {
+            self.retry_held = 0.;
+        }
+
+        self.handle_ui_messages(data.clone()).await;
+
+        if self.paused {
+            self.update_pause(data);
+        } else if self.started && !self.ended && binds.is_pressed(Action::Pause) {
+            self.pause(data);
         }
     }
 
@@ -576,8 +1213,10 @@ impl Screen for Gameplay<CatchRuleset> {
             );
 
             let mut radius = self.chart.fruit_radius * self.scale(data.clone());
-            if fruit.small {
-                radius /= 2.0;
+            match fruit.kind {
+                FruitKind::Fruit | FruitKind::Banana => {}
+                FruitKind::Droplet => radius /= 2.0,
+                FruitKind::TinyDroplet => radius /= 4.0,
             }
 
             if y + radius <= 0. {
@@ -589,11 +1228,18 @@ impl Screen for Gameplay<CatchRuleset> {
                 continue;
             }
 
-            let color = if fruit.hyper.is_some() {
+            let mut color = if fruit.hyper.is_some() {
                 RED
+            } else if fruit.kind == FruitKind::Banana {
+                YELLOW
             } else {
                 self.drawable_fruit_color(fruit.color)
             };
+            if self.recorder.mods.contains(&Mod::Hidden) {
+                // Fade out between 40% and 70% of the way down to the catcher.
+                color.a *=
+                    math::clamped_remap(self.catcher_y() * 0.4, self.catcher_y() * 0.7, 1., 0., y);
+            }
             draw_texture_ex(
                 data.fruit,
                 self.playfield_to_screen_x(fruit.position, data.clone()) - radius,
@@ -698,6 +1344,10 @@ impl Screen for Gameplay<CatchRuleset> {
             }
         }
 
+        if self.recorder.mods.contains(&Mod::Flashlight) {
+            self.draw_flashlight(data.clone());
+        }
+
         draw_text(
             &format!("{:.2}%", self.recorder.accuracy * 100.),
             screen_width() - 116.,
@@ -729,5 +1379,49 @@ impl Screen for Gameplay<CatchRuleset> {
             36,
             WHITE,
         );
+
+        if let Some(practice) = &self.practice {
+            self.draw_practice(practice);
+        }
+
+        if self.replay_type == ReplayType::Record && self.replay.input_mode == InputMode::Touch {
+            input::draw_touch_zones();
+        }
+
+        if self.skip_target().is_some() {
+            self.skip_button.draw(data.clone());
+        }
+
+        if self.retry_held > 0. {
+            draw_rectangle(
+                0.,
+                0.,
+                screen_width(),
+                screen_height(),
+                Color {
+                    a: self.retry_held / RETRY_HOLD,
+                    ..BLACK
+                },
+            );
+        }
+
+        if let Some(pause_menu) = &self.pause_menu {
+            draw_rectangle(
+                0.,
+                0.,
+                screen_width(),
+                screen_height(),
+                Color { a: 0.5, ..BLACK },
+            );
+            pause_menu.draw(data);
+        } else if let Some(countdown) = self.resume_countdown {
+            draw_text_centered(
+                &format!("{}", countdown.ceil()),
+                screen_width() / 2.,
+                screen_height() / 2.,
+                96,
+                WHITE,
+            );
+        }
     }
 }
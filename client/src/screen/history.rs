@@ -0,0 +1,251 @@
+use super::{
+    game::{GameMessage, SharedGameData},
+    get_charts,
+    select::SelectScreen,
+    Screen,
+};
+use crate::{
+    leaderboard::{self, LeaderboardEntry, PlayStats},
+    mods, LogType,
+};
+use aether::log;
+use async_trait::async_trait;
+use egui_macroquad::egui;
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+/// Number of plays listed under top plays.
+const TOP_PLAYS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    Date,
+    Pp,
+    Accuracy,
+    Score,
+    Combo,
+}
+
+impl SortBy {
+    const ALL: [SortBy; 5] = [
+        SortBy::Date,
+        SortBy::Pp,
+        SortBy::Accuracy,
+        SortBy::Score,
+        SortBy::Combo,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            SortBy::Date => "Date",
+            SortBy::Pp => "pp",
+            SortBy::Accuracy => "Accuracy",
+            SortBy::Score => "Score",
+            SortBy::Combo => "Combo",
+        }
+    }
+}
+
+/// A local play with the names of what was played.
+struct Play {
+    chart: String,
+    difficulty: String,
+    entry: LeaderboardEntry,
+}
+
+fn format_date(played_at: Option<u64>) -> String {
+    played_at
+        .and_then(|played_at| time::OffsetDateTime::from_unix_timestamp(played_at as i64).ok())
+        .and_then(|date_time| {
+            date_time
+                .format(
+                    &time::format_description::parse("[year]-[month]-[day] [hour]:[minute]")
+                        .unwrap(),
+                )
+                .ok()
+        })
+        .unwrap_or_else(|| "Unknown".to_owned())
+}
+
+fn format_duration(seconds: f32) -> String {
+    let minutes = (seconds / 60.) as u32;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Every local play, with filtering, sorting and totals.
+pub struct HistoryScreen {
+    plays: Vec<Play>,
+    stats: PlayStats,
+    /// Indices into `plays`, see [`leaderboard::top_plays`].
+    top_plays: Vec<usize>,
+    filter: String,
+    sort_by: SortBy,
+    ascending: bool,
+    back: bool,
+}
+
+impl HistoryScreen {
+    pub async fn new(data: SharedGameData) -> Self {
+        let names = get_charts(data.clone())
+            .into_iter()
+            .flat_map(|chart| {
+                chart
+                    .difficulties
+                    .into_iter()
+                    .map(move |diff| (diff.hash, (chart.title.clone(), diff.name)))
+            })
+            .collect::<HashMap<_, _>>();
+        let entries = data
+            .state_mut()
+            .leaderboard
+            .query_history()
+            .await
+            .unwrap_or_else(|e| {
+                log!(LogType::General, "Couldn't load play history: {}", e);
+                Vec::new()
+            });
+
+        HistoryScreen {
+            stats: PlayStats::new(&entries),
+            top_plays: leaderboard::top_plays(&entries, TOP_PLAYS),
+            plays: entries
+                .into_iter()
+                .map(|entry| {
+                    let (chart, difficulty) = entry
+                        .diff_hash
+                        .as_ref()
+                        .and_then(|diff_hash| names.get(diff_hash))
+                        .cloned()
+                        .unwrap_or_else(|| ("Unknown".to_owned(), "Unknown".to_owned()));
+                    Play {
+                        chart,
+                        difficulty,
+                        entry,
+                    }
+                })
+                .collect(),
+            filter: String::new(),
+            sort_by: SortBy::Date,
+            ascending: false,
+            back: false,
+        }
+    }
+
+    /// Plays matching the filter, in the chosen order.
+    fn shown_plays(&self) -> Vec<&Play> {
+        let filter = self.filter.to_lowercase();
+        let mut plays = self
+            .plays
+            .iter()
+            .filter(|play| {
+                format!(
+                    "{} {} {}",
+                    play.chart,
+                    play.difficulty,
+                    mods::badges(&play.entry.mods)
+                )
+                .to_lowercase()
+                .contains(&filter)
+            })
+            .collect::<Vec<_>>();
+        plays.sort_by(|a, b| {
+            let (a, b) = (&a.entry, &b.entry);
+            match self.sort_by {
+                SortBy::Date => a.played_at.cmp(&b.played_at),
+                SortBy::Pp => a.pp.total_cmp(&b.pp),
+                SortBy::Accuracy => a.accuracy.total_cmp(&b.accuracy),
+                SortBy::Score => a.score.cmp(&b.score),
+                SortBy::Combo => a.top_combo.cmp(&b.top_combo),
+            }
+        });
+        if !self.ascending {
+            plays.reverse();
+        }
+        plays
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for HistoryScreen {
+    async fn update(&mut self, data: SharedGameData) {
+        egui_macroquad::ui(|egui_ctx| {
+            egui::Window::new("History")
+                .collapsible(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0., 0.))
+                .show(egui_ctx, |ui| {
+                    ui.label(format!(
+                        "{} plays, {} played, {:.2}% average accuracy",
+                        self.stats.plays,
+                        format_duration(self.stats.play_time),
+                        self.stats.average_accuracy * 100.
+                    ));
+
+                    ui.collapsing("Top plays", |ui| {
+                        for &idx in &self.top_plays {
+                            let play = &self.plays[idx];
+                            ui.label(format!(
+                                "{:.0}pp {} [{}] {:.2}%",
+                                play.entry.pp,
+                                play.chart,
+                                play.difficulty,
+                                play.entry.accuracy * 100.
+                            ));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Filter");
+                        ui.text_edit_singleline(&mut self.filter);
+                        egui::ComboBox::from_label("Sort by")
+                            .selected_text(self.sort_by.name())
+                            .show_ui(ui, |ui| {
+                                for sort_by in SortBy::ALL {
+                                    ui.selectable_value(&mut self.sort_by, sort_by, sort_by.name());
+                                }
+                            });
+                        ui.checkbox(&mut self.ascending, "Ascending");
+                    });
+
+                    let plays = self.shown_plays();
+                    egui::ScrollArea::vertical()
+                        .max_height(screen_height() * 0.6)
+                        .show(ui, |ui| {
+                            egui::Grid::new("history").striped(true).show(ui, |ui| {
+                                for header in
+                                    ["Date", "Chart", "Mods", "Grade", "Accuracy", "Combo", "pp"]
+                                {
+                                    ui.strong(header);
+                                }
+                                ui.end_row();
+
+                                for play in plays {
+                                    let entry = &play.entry;
+                                    ui.label(format_date(entry.played_at));
+                                    ui.label(format!("{} [{}]", play.chart, play.difficulty));
+                                    ui.label(mods::badges(&entry.mods));
+                                    ui.label(entry.grade.name());
+                                    ui.label(format!("{:.2}%", entry.accuracy * 100.));
+                                    ui.label(format!("{}x", entry.top_combo));
+                                    ui.label(format!("{:.0}", entry.pp));
+                                    ui.end_row();
+                                }
+                            });
+                        });
+
+                    if ui.button("Back").clicked() {
+                        self.back = true;
+                    }
+                });
+        });
+
+        if self.back || is_key_pressed(KeyCode::Escape) {
+            data.broadcast(GameMessage::change_screen(
+                SelectScreen::new(data.clone()).await,
+            ));
+        }
+    }
+
+    fn draw(&self, _data: SharedGameData) {
+        egui_macroquad::draw();
+    }
+}
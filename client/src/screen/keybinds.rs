@@ -0,0 +1,54 @@
+use super::{
+    game::{GameMessage, SharedGameData},
+    overlay::KeyBindEditor,
+    select::SelectScreen,
+    Screen,
+};
+use crate::config::set_value;
+use async_trait::async_trait;
+use egui_macroquad::egui;
+
+/// Lets the player bind their own keys, shown when picking custom bindings on first launch.
+pub struct KeyBindScreen {
+    editor: KeyBindEditor,
+    done: bool,
+}
+
+impl KeyBindScreen {
+    pub fn new(data: SharedGameData) -> Self {
+        KeyBindScreen {
+            editor: KeyBindEditor::new(data),
+            done: false,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for KeyBindScreen {
+    async fn update(&mut self, data: SharedGameData) {
+        egui_macroquad::ui(|egui_ctx| {
+            egui::Window::new("Key Bindings")
+                .collapsible(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0., 0.))
+                .show(egui_ctx, |ui| {
+                    self.editor.ui(ui, data.clone());
+                    if ui.button("Done").clicked() {
+                        self.done = true;
+                    }
+                });
+        });
+
+        if self.done {
+            set_value("first_time", false);
+            // Saves the bindings even if the defaults were kept.
+            set_value("binds", &data.state().binds);
+            data.broadcast(GameMessage::change_screen(
+                SelectScreen::new(data.clone()).await,
+            ));
+        }
+    }
+
+    fn draw(&self, _data: SharedGameData) {
+        egui_macroquad::draw();
+    }
+}
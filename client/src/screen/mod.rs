@@ -1,20 +1,20 @@
-use self::{
-    game::{GameMessage, SharedGameData},
-    gameplay::Mod,
-};
+use self::game::{GameMessage, SharedGameData};
 use crate::{
     azusa::{ClientPacket, ServerPacket},
     cache::Cache,
     chat::Chat,
     config::{self, KeyBinds},
+    difficulty::DifficultyCache,
+    input::{Gamepads, InputMode},
     leaderboard::Leaderboard,
+    library::ChartLibrary,
+    mods::Mod,
     promise::PromiseExecutor,
+    score::ScoringModel,
+    LogType,
 };
+use aether::log;
 use async_trait::async_trait;
-use gluesql::{
-    prelude::{Glue, Payload},
-    sled_storage::SledStorage,
-};
 use kira::{
     manager::AudioManager,
     sound::static_sound::{StaticSoundData, StaticSoundHandle},
@@ -23,8 +23,11 @@ use kira::{
 use macroquad::prelude::*;
 use std::cell::{Cell, Ref, RefCell, RefMut};
 
+pub mod fail;
 pub mod game;
 pub mod gameplay;
+pub mod history;
+pub mod keybinds;
 pub mod overlay;
 pub mod result;
 pub mod select;
@@ -33,55 +36,13 @@ pub mod visualizer;
 
 // TODO move game.rs and GameState and GameData into a game module.
 
-#[derive(Debug, Clone)]
-pub struct DifficultyInfo {
-    pub id: u32,
-    pub name: String,
-}
-
-#[derive(Debug, Clone)]
-pub struct ChartInfo {
-    pub id: u32,
-    pub title: String,
-    pub difficulties: Vec<DifficultyInfo>,
-}
+pub use crate::library::{ChartInfo, DifficultyInfo};
 
 pub fn get_charts(data: SharedGameData) -> Vec<ChartInfo> {
-    let mut cache_db = data.chart_db.borrow_mut();
-    match cache_db.execute("SELECT id, title FROM charts").unwrap() {
-        Payload::Select { labels: _, rows } => rows
-            .into_iter()
-            .map(|columns| {
-                let id: i64 = columns[0].clone().try_into().unwrap();
-                let title = columns[1].clone().try_into().unwrap();
-
-                let difficulties = match cache_db
-                    .execute(format!(
-                        "SELECT id, title FROM difficulties WHERE chart_id = {}",
-                        id
-                    ))
-                    .unwrap()
-                {
-                    Payload::Select { labels: _, rows } => rows
-                        .into_iter()
-                        .map(|columns| {
-                            let id: i64 = columns[0].clone().try_into().unwrap();
-                            let name = columns[1].clone().try_into().unwrap();
-                            DifficultyInfo { id: id as _, name }
-                        })
-                        .collect(),
-                    _ => unreachable!(),
-                };
-
-                ChartInfo {
-                    id: id as _,
-                    title,
-                    difficulties,
-                }
-            })
-            .collect(),
-        _ => unreachable!(),
-    }
+    data.library.borrow_mut().charts().unwrap_or_else(|e| {
+        log!(LogType::General, "Couldn't load charts: {}", e);
+        Vec::new()
+    })
 }
 
 #[async_trait(?Send)]
@@ -133,6 +94,7 @@ pub struct GameData {
 
     pub audio_cache: Cache<StaticSoundData>,
     pub image_cache: Cache<Texture2D>,
+    pub difficulty_cache: DifficultyCache,
 
     time: Cell<f32>,
     predicted_time: Cell<f32>,
@@ -143,6 +105,11 @@ pub struct GameData {
     /// Playfield size as a percent of the screen width \[0; 1\].
     playfield_size: Cell<f32>,
     max_stack: Cell<u32>,
+    /// Scoring model used for new plays and to rank leaderboards.
+    scoring_model: Cell<ScoringModel>,
+    /// Input mode used for new plays.
+    input_mode: Cell<InputMode>,
+    gamepads: RefCell<Gamepads>,
 
     state: RefCell<GameState>,
     promises: RefCell<PromiseExecutor>,
@@ -152,7 +119,7 @@ pub struct GameData {
     mods: RefCell<Vec<Mod>>,
     rate: Cell<f32>,
 
-    pub chart_db: RefCell<Glue<gluesql::sled_storage::sled::IVec, SledStorage>>,
+    pub library: RefCell<ChartLibrary>,
 }
 
 impl GameData {
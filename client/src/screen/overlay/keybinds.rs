@@ -0,0 +1,96 @@
+use crate::{
+    config::{self, key_name, Action, KeyBinds},
+    screen::game::SharedGameData,
+};
+use egui_macroquad::egui;
+use macroquad::prelude::*;
+
+/// Lists the keys of every action. Changes are saved right away.
+pub struct KeyBindEditor {
+    binds: KeyBinds,
+    /// Action waiting for a key to be pressed to add it.
+    listening: Option<Action>,
+}
+
+impl KeyBindEditor {
+    pub fn new(data: SharedGameData) -> Self {
+        KeyBindEditor {
+            binds: data.state().binds.clone(),
+            listening: None,
+        }
+    }
+
+    /// The next key pressed is added to the action being listened for.
+    pub fn is_listening(&self) -> bool {
+        self.listening.is_some()
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, data: SharedGameData) {
+        let mut changed = false;
+
+        if let Some(action) = self.listening {
+            if let Some(key) = get_last_key_pressed() {
+                let mut keys = self.binds.keys(action).to_vec();
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+                self.binds.set_keys(action, keys);
+                self.listening = None;
+                changed = true;
+            }
+        }
+
+        egui::Grid::new("key_binds").show(ui, |ui| {
+            for action in Action::ALL {
+                ui.label(action.name());
+                ui.horizontal(|ui| {
+                    // Clicking a key unbinds it.
+                    let mut keys = self.binds.keys(action).to_vec();
+                    let len = keys.len();
+                    keys.retain(|&key| !ui.button(key_name(key)).clicked());
+                    if keys.len() != len {
+                        self.binds.set_keys(action, keys);
+                        changed = true;
+                    }
+
+                    if self.listening == Some(action) {
+                        if ui.button("Press a key... (cancel)").clicked() {
+                            self.listening = None;
+                        }
+                    } else if ui.button("+").clicked() {
+                        self.listening = Some(action);
+                    }
+                });
+                ui.end_row();
+            }
+        });
+
+        for (key, a, b) in self.binds.conflicts() {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!(
+                    "{} is bound to both {} and {}",
+                    key_name(key),
+                    a.name(),
+                    b.name()
+                ),
+            );
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Left-handed preset").clicked() {
+                self.binds = KeyBinds::left_handed();
+                changed = true;
+            }
+            if ui.button("Right-handed preset").clicked() {
+                self.binds = KeyBinds::right_handed();
+                changed = true;
+            }
+        });
+
+        if changed {
+            config::set_value("binds", &self.binds);
+            data.state_mut().binds = self.binds.clone();
+        }
+    }
+}
@@ -2,13 +2,17 @@ use super::game::SharedGameData;
 use async_trait::async_trait;
 
 mod chat;
+mod keybinds;
 mod login;
 mod mods;
+mod ranking;
 mod settings;
 
 pub use self::login::Login;
 pub use chat::Chat;
+pub use keybinds::KeyBindEditor;
 pub use mods::Mods;
+pub use ranking::Ranking;
 pub use settings::Settings;
 
 #[async_trait(?Send)]
@@ -22,6 +26,7 @@ pub enum OverlayEnum {
     Settings(Settings),
     Login(Login),
     Mods(Mods),
+    Ranking(Ranking),
 }
 
 #[async_trait(?Send)]
@@ -32,6 +37,7 @@ impl Overlay for OverlayEnum {
             OverlayEnum::Settings(s) => s.update(data),
             OverlayEnum::Login(l) => l.update(data),
             OverlayEnum::Mods(m) => m.update(data),
+            OverlayEnum::Ranking(r) => r.update(data),
         }
     }
 
@@ -41,6 +47,7 @@ impl Overlay for OverlayEnum {
             OverlayEnum::Settings(s) => s.draw(data),
             OverlayEnum::Login(l) => l.draw(data),
             OverlayEnum::Mods(m) => m.draw(data),
+            OverlayEnum::Ranking(r) => r.draw(data),
         }
     }
 }
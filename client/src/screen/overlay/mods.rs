@@ -1,23 +1,51 @@
 use super::Overlay;
-use crate::screen::{game::SharedGameData, gameplay::Mod};
+use crate::{mods::Mod, screen::game::SharedGameData};
 use egui_macroquad::egui;
 
 pub struct Mods {
     rate: f32,
+    no_fail: bool,
+    hidden: bool,
+    flashlight: bool,
 }
 
 impl Mods {
     pub fn new(data: SharedGameData) -> Self {
-        let mut mods = Mods { rate: 1.0 };
+        let mut mods = Mods {
+            rate: 1.0,
+            no_fail: false,
+            hidden: false,
+            flashlight: false,
+        };
 
         for to_apply in data.mods.borrow().iter() {
             match to_apply {
                 Mod::Rate(rate) => mods.rate = *rate,
+                Mod::NoFail => mods.no_fail = true,
+                Mod::Hidden => mods.hidden = true,
+                Mod::Flashlight => mods.flashlight = true,
             }
         }
 
         mods
     }
+
+    fn to_mods(&self) -> Vec<Mod> {
+        let mut mods = Vec::new();
+        if self.rate != 1.0 {
+            mods.push(Mod::Rate(self.rate));
+        }
+        if self.no_fail {
+            mods.push(Mod::NoFail);
+        }
+        if self.hidden {
+            mods.push(Mod::Hidden);
+        }
+        if self.flashlight {
+            mods.push(Mod::Flashlight);
+        }
+        mods
+    }
 }
 
 impl Overlay for Mods {
@@ -38,15 +66,20 @@ impl Overlay for Mods {
                             .show_value(true)
                             .suffix("x"),
                     );
+                    ui.checkbox(&mut self.no_fail, "No Fail");
+                    ui.checkbox(&mut self.hidden, "Hidden");
+                    ui.checkbox(&mut self.flashlight, "Flashlight");
+
+                    let mods = self.to_mods();
+                    ui.label(format!(
+                        "Score Multiplier: {:.2}x",
+                        crate::mods::multiplier(&mods)
+                    ));
                 });
         });
 
         // Slow but I cba to add a dirty flag.
-        let mods = &mut *data.mods.borrow_mut();
-        mods.clear();
-        if self.rate != 1.0 {
-            mods.push(Mod::Rate(self.rate));
-        }
+        *data.mods.borrow_mut() = self.to_mods();
     }
 
     fn draw(&self, _data: SharedGameData) {
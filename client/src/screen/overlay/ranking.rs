@@ -0,0 +1,55 @@
+use super::Overlay;
+use crate::{azusa::RankingEntry, screen::game::SharedGameData};
+use egui_macroquad::egui;
+
+/// The global performance ranking, requested from Azusa when the overlay opens.
+pub struct Ranking {
+    /// `None` until Azusa responds.
+    entries: Option<Vec<RankingEntry>>,
+}
+
+impl Ranking {
+    pub fn new() -> Self {
+        Ranking { entries: None }
+    }
+
+    pub fn set_entries(&mut self, entries: Vec<RankingEntry>) {
+        self.entries = Some(entries);
+    }
+}
+
+impl Overlay for Ranking {
+    fn update(&mut self, _data: SharedGameData) {
+        egui_macroquad::ui(|egui_ctx| {
+            egui::Window::new("Ranking")
+                .collapsible(false)
+                .frame(
+                    egui::Frame::default()
+                        .fill(egui::Color32::from_rgba_unmultiplied(64, 64, 64, 240)),
+                )
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0., 0.))
+                .show(egui_ctx, |ui| match &self.entries {
+                    Some(entries) if entries.is_empty() => {
+                        ui.label("Nobody is ranked yet");
+                    }
+                    Some(entries) => {
+                        egui::Grid::new("ranking").striped(true).show(ui, |ui| {
+                            for (idx, entry) in entries.iter().enumerate() {
+                                ui.label(format!("#{}", idx + 1));
+                                ui.label(&entry.username);
+                                ui.label(format!("{:.0}pp", entry.pp));
+                                ui.end_row();
+                            }
+                        });
+                    }
+                    None => {
+                        ui.label("Loading...");
+                    }
+                });
+        });
+    }
+
+    fn draw(&self, _data: SharedGameData) {
+        egui_macroquad::draw();
+    }
+}
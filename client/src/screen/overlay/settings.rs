@@ -1,6 +1,9 @@
-use super::Overlay;
+use super::{KeyBindEditor, Overlay};
 use crate::{
     config,
+    input::InputMode,
+    library,
+    score::ScoringModel,
     screen::game::{GameMessage, SharedGameData},
 };
 use egui_macroquad::egui;
@@ -14,6 +17,10 @@ pub struct Settings {
 
     max_stack: u32,
     playfield_size: u32,
+    scoring_model: ScoringModel,
+    input_mode: InputMode,
+    key_binds: KeyBindEditor,
+    library_path: String,
 }
 
 impl Settings {
@@ -25,8 +32,17 @@ impl Settings {
             offset_ms: (data.offset.get() * 1000.) as i32,
             max_stack: data.max_stack.get(),
             playfield_size: (data.playfield_size.get() * 100.) as u32,
+            scoring_model: data.scoring_model.get(),
+            input_mode: data.input_mode.get(),
+            key_binds: KeyBindEditor::new(data),
+            library_path: library::library_path(),
         }
     }
+
+    /// Waiting for a key to bind, so global shortcuts shouldn't react to it.
+    pub fn is_listening(&self) -> bool {
+        self.key_binds.is_listening()
+    }
 }
 
 impl Overlay for Settings {
@@ -127,6 +143,42 @@ impl Overlay for Settings {
                         data.max_stack.set(self.max_stack);
                         config::set_value("max_stack", self.max_stack);
                     }
+
+                    egui::ComboBox::from_label("Scoring")
+                        .selected_text(self.scoring_model.name())
+                        .show_ui(ui, |ui| {
+                            for model in ScoringModel::ALL {
+                                ui.selectable_value(&mut self.scoring_model, model, model.name());
+                            }
+                        });
+                    if self.scoring_model != data.scoring_model.get() {
+                        data.scoring_model.set(self.scoring_model);
+                        config::set_value("scoring_model", self.scoring_model);
+                    }
+
+                    egui::ComboBox::from_label("Input")
+                        .selected_text(self.input_mode.name())
+                        .show_ui(ui, |ui| {
+                            for mode in InputMode::ALL {
+                                ui.selectable_value(&mut self.input_mode, mode, mode.name());
+                            }
+                        });
+                    if self.input_mode != data.input_mode.get() {
+                        data.input_mode.set(self.input_mode);
+                        config::set_value("input_mode", self.input_mode);
+                    }
+
+                    ui.collapsing("Key Bindings", |ui| {
+                        self.key_binds.ui(ui, data.clone());
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Library");
+                        if ui.text_edit_singleline(&mut self.library_path).changed() {
+                            config::set_value("library_path", &self.library_path);
+                        }
+                    });
+                    ui.label("Rescan the library on the song select screen to apply.");
                 });
         });
     }
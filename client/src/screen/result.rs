@@ -1,18 +1,112 @@
 use super::{
     game::{GameMessage, SharedGameData},
-    gameplay::Replay,
+    gameplay::{Gameplay, Replay},
     select::SelectScreen,
     Screen,
 };
 use crate::{
-    draw_text_centered,
-    rulesets::Ruleset,
-    score::{self, Score},
+    analytics::{self, HitEvent},
+    draw_text_centered, math, mods, replays,
+    rulesets::{catch::CatchRuleset, Ruleset},
+    score::{self, Grade, Score},
+    ui::{
+        menubutton::{MenuButton, MenuButtonMessage, Popout},
+        Message, MessageData, UiElement,
+    },
 };
 use async_trait::async_trait;
-use instant::SystemTime;
 use macroquad::prelude::*;
-use serde::Serialize;
+
+/// Number of bins in the catch offset histogram.
+const HISTOGRAM_BINS: usize = 21;
+
+/// How close in pixels a click has to be to a miss on the miss strip.
+const MISS_CLICK_DISTANCE: f32 = 5.;
+
+fn histogram_bounds() -> Rect {
+    Rect::new(20., screen_height() - 170., 300., 150.)
+}
+
+fn graph_bounds() -> Rect {
+    Rect::new(340., screen_height() - 170., 600., 110.)
+}
+
+fn miss_strip_bounds() -> Rect {
+    Rect::new(340., screen_height() - 45., 600., 25.)
+}
+
+/// Draws the catch offset histogram, with the center of the catcher in the middle.
+fn draw_offset_histogram(bounds: Rect, histogram: &[u32]) {
+    draw_rectangle(
+        bounds.x,
+        bounds.y,
+        bounds.w,
+        bounds.h,
+        Color::new(0., 0., 0., 0.4),
+    );
+    let max = histogram.iter().copied().max().unwrap_or(0).max(1);
+    let bar_width = bounds.w / histogram.len() as f32;
+    for (idx, &count) in histogram.iter().enumerate() {
+        let bar_height = count as f32 / max as f32 * bounds.h;
+        draw_rectangle(
+            bounds.x + idx as f32 * bar_width,
+            bounds.y + bounds.h - bar_height,
+            bar_width - 1.,
+            bar_height,
+            WHITE,
+        );
+    }
+    draw_line(
+        bounds.x + bounds.w / 2.,
+        bounds.y,
+        bounds.x + bounds.w / 2.,
+        bounds.y + bounds.h,
+        1.,
+        RED,
+    );
+    draw_text("Catch offset", bounds.x, bounds.y - 4., 20., WHITE);
+}
+
+/// Draws `points` of (time, value \[0; 1\]) as a line, with time going from 0 to `length`.
+fn draw_time_graph(bounds: Rect, length: f32, points: &[(f32, f32)], color: Color) {
+    for [(a_time, a), (b_time, b)] in points.array_windows::<2>() {
+        draw_line(
+            bounds.x + a_time / length * bounds.w,
+            bounds.y + (1. - a) * bounds.h,
+            bounds.x + b_time / length * bounds.w,
+            bounds.y + (1. - b) * bounds.h,
+            2.,
+            color,
+        );
+    }
+}
+
+/// Draws a tick for every miss, with time going from 0 to `length`.
+fn draw_miss_strip(bounds: Rect, length: f32, misses: &[f32]) {
+    draw_rectangle(
+        bounds.x,
+        bounds.y,
+        bounds.w,
+        bounds.h,
+        Color::new(0., 0., 0., 0.4),
+    );
+    for &time in misses {
+        let x = bounds.x + time / length * bounds.w;
+        draw_line(x, bounds.y, x, bounds.y + bounds.h, 2., RED);
+    }
+}
+
+/// Color a grade is drawn with.
+pub fn grade_color(grade: Grade) -> Color {
+    match grade {
+        Grade::SilverSS | Grade::SilverS => Color::new(0.85, 0.85, 0.9, 1.0),
+        Grade::SS | Grade::S => GOLD,
+        Grade::A => GREEN,
+        Grade::B => BLUE,
+        Grade::C => PURPLE,
+        Grade::D => RED,
+    }
+}
 
 pub struct ResultScreen<R: Ruleset> {
     title: String,
@@ -20,64 +114,125 @@ pub struct ResultScreen<R: Ruleset> {
 
     score: Score<R::Judgement>,
     replay: Replay<R::Input, R::SyncFrame>,
+    /// Name in the replay library, once the replay is stored.
+    replay_name: Option<String>,
+    hit_events: Vec<HitEvent<R::Judgement>>,
+    /// Chart time of the last judged object.
+    length: f32,
+    histogram: Vec<u32>,
+    misses: Vec<f32>,
+
+    save_replay: MenuButton,
+    rx: flume::Receiver<Message>,
+    /// Outcome of saving the replay, shown below the save button.
+    save_status: Option<String>,
 }
 
 impl<R: Ruleset> ResultScreen<R> {
     pub fn new(
         score: Score<R::Judgement>,
         replay: Replay<R::Input, R::SyncFrame>,
+        replay_name: Option<String>,
+        hit_events: Vec<HitEvent<R::Judgement>>,
         title: String,
         difficulty: String,
     ) -> Self {
+        let (tx, rx) = flume::unbounded();
         ResultScreen {
             title,
             difficulty,
             score,
             replay,
+            replay_name,
+            length: hit_events.iter().map(|event| event.time).fold(1., f32::max),
+            histogram: analytics::offset_histogram(&hit_events, HISTOGRAM_BINS),
+            misses: analytics::miss_times(&hit_events),
+            hit_events,
+            save_replay: MenuButton::new(
+                "save_replay".to_string(),
+                vec!["Save replay (F2)".to_string()],
+                Popout::None,
+                Rect::new(screen_width() - 305., 5., 300., 100.),
+                tx,
+                false,
+            ),
+            rx,
+            save_status: None,
+        }
+    }
+
+    /// Saves the replay to the replay library, or downloads it on web.
+    async fn save_replay(&mut self) {
+        let content = replays::encode(&self.replay);
+        let stored = match self.replay_name.clone() {
+            Some(name) => Ok(name),
+            None => {
+                replays::store(
+                    &replays::replay_name(&self.title, &self.difficulty, self.replay.start),
+                    &content,
+                )
+                .await
+            }
+        };
+        self.save_status = Some(
+            match stored.and_then(|name| replays::export(&name, &content).map(|_| name)) {
+                Ok(name) => {
+                    let status = format!("Saved as \"{}\"", name);
+                    self.replay_name = Some(name);
+                    status
+                }
+                Err(e) => format!("Couldn't save replay: {}", e),
+            },
+        );
+    }
+
+    /// Miss on the miss strip under the mouse, if any.
+    fn hovered_miss(&self) -> Option<f32> {
+        let bounds = miss_strip_bounds();
+        let (mouse_x, mouse_y) = mouse_position();
+        if !bounds.contains(vec2(mouse_x, mouse_y)) {
+            return None;
         }
+        self.misses.iter().copied().find(|&time| {
+            (bounds.x + time / self.length * bounds.w - mouse_x).abs() <= MISS_CLICK_DISTANCE
+        })
     }
 }
 
 #[async_trait(?Send)]
-impl<R: Ruleset> Screen for ResultScreen<R> {
+impl Screen for ResultScreen<CatchRuleset> {
     async fn update(&mut self, data: SharedGameData) {
+        if is_mouse_button_pressed(MouseButton::Left) {
+            if let Some(time) = self.hovered_miss() {
+                data.broadcast(GameMessage::load_screen({
+                    let data = data.clone();
+                    let title = self.title.clone();
+                    let difficulty = self.difficulty.clone();
+                    let replay = self.replay.clone();
+                    async move {
+                        Gameplay::with_replay_at(data, &title, &difficulty, replay, time).await
+                    }
+                }));
+            }
+        }
+
         if is_key_pressed(KeyCode::Escape) {
             data.broadcast(GameMessage::change_screen(
                 SelectScreen::new(data.clone()).await,
             ));
         }
 
+        self.save_replay.update(data.clone());
+        for message in self.rx.drain().collect::<Vec<_>>() {
+            self.save_replay.handle_message(&message);
+            if message.target == self.save_replay.id {
+                if let MessageData::MenuButton(MenuButtonMessage::Selected) = message.data {
+                    self.save_replay().await;
+                }
+            }
+        }
         if is_key_pressed(KeyCode::F2) {
-            let date_time = time::OffsetDateTime::from_unix_timestamp(
-                self.replay
-                    .start
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i64,
-            )
-            .unwrap();
-            let replay_name = format!(
-                    "{} - {} ({}).crp",
-                    self.title,
-                    self.difficulty,
-                    date_time
-                        .format(
-                            &time::format_description::parse(
-                                "[year repr:full] [month repr:short padding:zero] [day] [hour repr:24 padding:zero]:[minute padding:zero]"
-                            )
-                            .unwrap()
-                        )
-                        .unwrap()
-                );
-            let mut ser = rmp_serde::Serializer::new(
-                std::fs::OpenOptions::new()
-                    .create_new(true)
-                    .write(true)
-                    .open(&replay_name)
-                    .unwrap(),
-            )
-            .with_binary();
-            self.replay.serialize(&mut ser).unwrap();
+            self.save_replay().await;
         }
     }
 
@@ -109,6 +264,22 @@ impl<R: Ruleset> Screen for ResultScreen<R> {
             36,
             WHITE,
         );
+        draw_text_centered(
+            self.score.grade.name(),
+            screen_width() - 150.,
+            screen_height() / 2. + 50.,
+            160,
+            grade_color(self.score.grade),
+        );
+        if !self.score.mods.is_empty() {
+            draw_text_centered(
+                &mods::badges(&self.score.mods),
+                screen_width() / 2.,
+                screen_height() / 2. - 55.,
+                36,
+                WHITE,
+            );
+        }
         draw_text_centered(
             &format!("{}x", self.score.top_combo),
             screen_width() / 2.,
@@ -129,6 +300,15 @@ impl<R: Ruleset> Screen for ResultScreen<R> {
             36,
             WHITE,
         );
+        for (idx, (judgement, count)) in self.score.judgements.iter().enumerate() {
+            draw_text(
+                &format!("{}: {}", judgement.name(), count),
+                20.,
+                screen_height() / 2. - 100. + 30. * idx as f32,
+                28.,
+                WHITE,
+            );
+        }
         draw_text_centered(
             &format!("{}", self.score.score),
             screen_width() / 2.,
@@ -143,5 +323,74 @@ impl<R: Ruleset> Screen for ResultScreen<R> {
             36,
             WHITE,
         );
+        draw_text_centered(
+            &format!("{:.0}pp", self.score.pp),
+            screen_width() / 2.,
+            screen_height() / 2. + 150.,
+            36,
+            WHITE,
+        );
+
+        self.save_replay.draw(data.clone());
+        if let Some(status) = &self.save_status {
+            let width = measure_text(status, None, 20, 1.).width;
+            draw_text(status, screen_width() - width - 5., 125., 20., WHITE);
+        }
+
+        draw_offset_histogram(histogram_bounds(), &self.histogram);
+
+        let graph = graph_bounds();
+        draw_rectangle(
+            graph.x,
+            graph.y,
+            graph.w,
+            graph.h,
+            Color::new(0., 0., 0., 0.4),
+        );
+        let hp = self
+            .hit_events
+            .iter()
+            .map(|event| (event.time, event.hp))
+            .collect::<Vec<_>>();
+        draw_time_graph(graph, self.length, &hp, GREEN);
+        // Accuracy rarely drops far, so the graph is scaled to the lowest point.
+        let min_accuracy = self
+            .hit_events
+            .iter()
+            .map(|event| event.accuracy)
+            .fold(1., f32::min)
+            .min(0.99);
+        let accuracy = self
+            .hit_events
+            .iter()
+            .map(|event| {
+                (
+                    event.time,
+                    math::remap(min_accuracy, 1., 0., 1., event.accuracy),
+                )
+            })
+            .collect::<Vec<_>>();
+        draw_time_graph(graph, self.length, &accuracy, YELLOW);
+        draw_text("HP", graph.x, graph.y - 4., 20., GREEN);
+        draw_text(
+            &format!("Accuracy ({:.2}% - 100%)", min_accuracy * 100.),
+            graph.x + 40.,
+            graph.y - 4.,
+            20.,
+            YELLOW,
+        );
+
+        let strip = miss_strip_bounds();
+        draw_miss_strip(strip, self.length, &self.misses);
+        draw_text(
+            match self.hovered_miss() {
+                Some(_) => "Click to watch this miss",
+                None => "Misses",
+            },
+            strip.x,
+            strip.y - 4.,
+            20.,
+            WHITE,
+        );
     }
 }
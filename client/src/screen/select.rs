@@ -1,183 +1,113 @@
-use std::{
-    cell::Cell,
-    collections::{BTreeMap, HashMap},
-};
+use std::{cell::Cell, collections::BTreeMap, sync::Arc};
 
 use super::{
     game::{GameMessage, SharedGameData},
     gameplay::Gameplay,
-    get_charts, ChartInfo, Screen,
+    get_charts,
+    history::HistoryScreen,
+    ChartInfo, Screen,
 };
 use crate::{
     azusa::{ClientPacket, ServerPacket},
     chart::Chart,
     convert::ConvertFrom,
-    draw_circle_range, draw_text_centered,
+    difficulty::{self, DifficultyAttributes},
+    draw_circle_range, draw_text_centered, import, library,
+    mods::{self, Mod},
+    practice::Section,
     promise::Promise,
-    score,
+    rulesets::{catch::CatchJudgement, JudgementResult},
+    score::{self, ScoringModel},
     ui::{
         expandablelist::{ExpandableList, ExpandableListMessage},
         menubutton::{MenuButton, MenuButtonMessage, Popout},
         menubuttonlist::MenuButtonList,
         Message, MessageData, UiElement,
     },
+    LogType,
 };
+use aether::log;
 use async_trait::async_trait;
 use kira::sound::static_sound::StaticSoundData;
 use macroquad::prelude::*;
-use noisy_float::prelude::R32;
 use num_format::{Locale, ToFormattedString};
 
-/// f takes progress [0-1] as input and result as output [0-1]
-fn draw_visualization(x: f32, y: f32, width: f32, height: f32, f: impl Fn(f32) -> f32) {
-    for i in 0..(height as u32) {
-        let progress = i as f32 / height as f32;
-        let value = f(progress);
-        draw_rectangle(
-            x,
-            y + i as f32,
-            width,
-            1.,
-            Color {
-                r: value,
-                g: value,
-                b: value,
-                a: 1.0,
-            },
-        )
-    }
-}
+/// Time in seconds the mods have to stay the same before the difficulty is calculated for them,
+/// so dragging the rate slider doesn't calculate every rate on the way.
+const DIFFICULTY_DEBOUNCE: f64 = 0.3;
 
-fn interpolate(max: f32, tree: &BTreeMap<R32, R32>) -> impl Fn(f32) -> f32 + '_ {
-    move |progress| {
-        let time = progress * max;
-        let pre = if let Some(v) = tree.iter().take_while(|(t, _)| t.raw() < time).last() {
-            (v.0.raw(), v.1.raw())
-        } else {
-            (0.0, 0.0)
-        };
-        let post = if let Some(v) = tree.iter().find(|(t, _)| t.raw() >= time) {
-            (v.0.raw(), v.1.raw())
-        } else {
-            (0.0, 0.0)
-        };
-        crate::math::remap(pre.0, post.0, pre.1, post.1, time)
-    }
+/// Where the strain timeline of the selected difficulty is drawn.
+fn strain_timeline_bounds() -> Rect {
+    Rect::new(5., screen_height() - 100., 400., 95.)
 }
 
-fn mean(data: &[f32]) -> f32 {
-    let sum = data.iter().sum::<f32>();
-    let count = data.len();
-
-    match count {
-        positive if positive > 0 => sum / count as f32,
-        _ => 0.,
+/// Draws `strains` as bars from left to right, scaled so the highest one fills `height`.
+fn draw_strain_timeline(x: f32, y: f32, width: f32, height: f32, strains: &[f32]) {
+    let max = strains.iter().copied().fold(0., f32::max);
+    if max <= 0. {
+        return;
     }
-}
-
-fn std_deviation(data: &[f32]) -> f32 {
-    let data_mean = mean(data);
-    let count = data.len();
-    if count > 0 {
-        let variance = data
-            .iter()
-            .map(|value| {
-                let diff = data_mean - (*value as f32);
-
-                diff * diff
-            })
-            .sum::<f32>()
-            / count as f32;
-
-        variance.sqrt()
-    } else {
-        0.
+    let bar_width = width / strains.len() as f32;
+    for (idx, strain) in strains.iter().enumerate() {
+        let bar_height = strain / max * height;
+        draw_rectangle(
+            x + bar_width * idx as f32,
+            y + height - bar_height,
+            bar_width,
+            bar_height,
+            Color::new(1., 1., 1., 0.4 + 0.6 * strain / max),
+        );
     }
 }
 
-pub struct ChartCalcData {
-    density: BTreeMap<R32, R32>,
-    angles: BTreeMap<R32, R32>,
-    angle_changes: BTreeMap<R32, R32>,
+/// Reads the `.osu` file of a difficulty.
+async fn load_beatmap(chart_title: &str, diff_name: &str) -> Vec<u8> {
+    library::load_file(&library::chart_file(
+        chart_title,
+        &format!("{}.osu", diff_name),
+    ))
+    .await
+    .unwrap()
 }
 
-impl ChartCalcData {
-    pub fn new(chart: &Chart) -> Self {
-        let mut chart_data = ChartCalcData {
-            density: BTreeMap::new(),
-            angles: BTreeMap::new(),
-            angle_changes: BTreeMap::new(),
-        };
-        for [a, b] in chart.fruits.array_windows::<2>() {
-            assert_ne!(a.time, b.time);
-            let time_to_hit = b.time - a.time;
-            // https://www.desmos.com/calculator/yt3tru6suf
-            // \frac{1}{1+e^{\frac{v}{100}\left(x-m\right)}}
-            fn density(diff_ms: f32) -> f32 {
-                const V: f32 = 3.0 / 100.;
-                const M: f32 = 166.0;
-                1. / (1. + (diff_ms * V - M * V).exp())
-            }
-
-            let angle = a.angle_to(b, chart.fall_time).to_degrees();
-
-            chart_data
-                .density
-                .insert(R32::new(b.time), R32::new(density(time_to_hit * 1000.)));
-            chart_data
-                .angles
-                .insert(R32::new(b.time), R32::new(angle.abs() / 90.));
-        }
-        for [a, b, c] in chart.fruits.array_windows::<3>() {
-            let angle_a = a.angle_to(b, chart.fall_time).to_degrees();
-            let angle_b = b.angle_to(c, chart.fall_time).to_degrees();
-
-            let angle_change = angle_a.max(angle_b) - angle_a.min(angle_b);
-
-            chart_data
-                .angle_changes
-                .insert(R32::new(b.time), R32::new(angle_change / 180.));
-        }
-        chart_data
-    }
-
-    pub fn avg_density(&self) -> f32 {
-        self.density.values().sum::<R32>().raw() / self.density.len() as f32
-    }
-
-    pub fn avg_angles(&self) -> f32 {
-        self.angles.values().sum::<R32>().raw() / self.angles.len() as f32
-    }
+fn convert_beatmap(beatmap_data: &[u8]) -> Chart {
+    let beatmap_content = std::str::from_utf8(beatmap_data).unwrap();
+    let beatmap =
+        osu_parser::load_content(beatmap_content, osu_parser::BeatmapParseOptions::default())
+            .unwrap();
+    Chart::convert_from(&beatmap)
+}
 
-    pub fn avg_angle_changes(&self) -> f32 {
-        self.angle_changes.values().sum::<R32>().raw() / self.angle_changes.len() as f32
+/// Per-kind judgement counts followed by mod badges, shown on leaderboard entries.
+fn entry_details(
+    judgements: &BTreeMap<JudgementResult<CatchJudgement>, u32>,
+    mods: &[Mod],
+) -> String {
+    let counts = score::judgement_counts(judgements)
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("/");
+    if mods.is_empty() {
+        counts
+    } else {
+        format!("{} {}", counts, mods::badges(mods))
     }
+}
 
-    pub fn star_rating(&self) -> f32 {
-        let density_difficulty = self.avg_density() * 10.;
-        let angle_difficulty = 1. - self.avg_angles();
-        let angle_changes_difficulty = self.avg_angle_changes();
-
-        let density_inconsistency =
-            std_deviation(&self.density.values().map(|f| f.raw()).collect::<Vec<_>>());
-
-        let mut anomalies = self
-            .density
-            .values()
-            .map(|v| v.raw() / self.avg_density())
-            .map(|v| v.max(1.))
-            .collect::<Vec<_>>();
-        anomalies.dedup();
-        let spikiness = anomalies.iter().sum::<f32>() / anomalies.len() as f32;
-
-        (1. + density_difficulty
-            * angle_difficulty
-            * angle_changes_difficulty.powi(2)
-            * density_inconsistency.sqrt()
-            * spikiness.sqrt()
-            * 100.)
-            .powf(1.5)
-            - 1.
+/// Score of a play under `model`, estimated if it was set with another model so leaderboards can be ranked with one model.
+fn ranked_score(
+    model: ScoringModel,
+    set_with: ScoringModel,
+    score: u32,
+    judgements: &BTreeMap<JudgementResult<CatchJudgement>, u32>,
+    top_combo: u32,
+    mods: &[Mod],
+) -> u32 {
+    if model == set_with {
+        score
+    } else {
+        model.estimate(judgements, top_combo, mods)
     }
 }
 
@@ -187,7 +117,18 @@ pub struct SelectScreen {
     selected_chart: usize,
     selected_difficulty: usize,
 
-    chart_data: Option<ChartCalcData>,
+    /// Difficulty of the selected difficulty with the active mods.
+    difficulty: Option<Arc<DifficultyAttributes>>,
+    /// Difficulty id and mods `difficulty` is for, or is being calculated for.
+    difficulty_key: Option<(u32, Vec<Mod>)>,
+    /// When to look up the difficulty, once the mods stopped changing.
+    difficulty_due: Option<f64>,
+    /// Difficulty being calculated, with the difficulty id and mods it's for.
+    calculated: Option<flume::Receiver<((u32, Vec<Mod>), Arc<DifficultyAttributes>)>>,
+    /// Part of the strain timeline picked for practice, as fractions of its width.
+    practice_range: Option<(f32, f32)>,
+    /// Where dragging across the strain timeline started, as a fraction of its width.
+    practice_drag: Option<f32>,
 
     scroll_vel: f32,
 
@@ -199,8 +140,18 @@ pub struct SelectScreen {
     scroll_target: Option<f32>,
 
     start: MenuButton,
+    practice: MenuButton,
+    history: MenuButton,
+    rescan: MenuButton,
+    import: MenuButton,
+    #[cfg(target_family = "wasm")]
+    import_folder: MenuButton,
+    /// Files being read after picking them to import.
+    #[cfg(target_family = "wasm")]
+    picked: Option<flume::Receiver<Vec<import::File>>>,
     pause: MenuButton,
-    loading_promise: Option<Promise<(StaticSoundData, Texture2D)>>,
+    /// Music and background of the selected chart, `None` if its music couldn't be found.
+    loading_promise: Option<Promise<Option<(StaticSoundData, Option<Texture2D>)>>>,
     started_map: Cell<bool>,
 }
 
@@ -209,31 +160,6 @@ impl SelectScreen {
         let (tx, rx) = flume::unbounded();
         let charts = get_charts(data.clone());
 
-        let diff_futures = charts.iter().flat_map(|chart| {
-            chart.difficulties.iter().map(|diff| async {
-                let beatmap_data =
-                    load_file(&format!("resources/{}/{}.osu", chart.title, diff.name))
-                        .await
-                        .unwrap();
-                let beatmap_content = std::str::from_utf8(&beatmap_data).unwrap();
-                let beatmap = osu_parser::load_content(
-                    beatmap_content,
-                    osu_parser::BeatmapParseOptions::default(),
-                )
-                .unwrap();
-                let chart_impl = crate::chart::Chart::convert_from(&beatmap);
-                (
-                    format!("{}-{}", chart.title, diff.name),
-                    ChartCalcData::new(&chart_impl),
-                )
-            })
-        });
-        let mut diffs = HashMap::new();
-        for diff_future in diff_futures {
-            let (key, value) = diff_future.await;
-            diffs.insert(key, value);
-        }
-
         let chart_list = ExpandableList::new(
             data,
             "button_list".to_string(),
@@ -241,11 +167,13 @@ impl SelectScreen {
             Rect::new(screen_width() - 400., 0., 400., 400.),
             tx.clone(),
         );
-        tx.send(Message {
-            target: chart_list.id.clone(),
-            data: MessageData::ExpandableList(ExpandableListMessage::Click(0)),
-        })
-        .unwrap();
+        if !charts.is_empty() {
+            tx.send(Message {
+                target: chart_list.id.clone(),
+                data: MessageData::ExpandableList(ExpandableListMessage::Click(0)),
+            })
+            .unwrap();
+        }
 
         SelectScreen {
             prev_selected_chart: usize::MAX,
@@ -271,11 +199,64 @@ impl SelectScreen {
                 tx.clone(),
                 false,
             ),
+            practice: MenuButton::new(
+                "practice".to_string(),
+                vec!["Practice".to_string()],
+                Popout::None,
+                Rect::new(
+                    screen_width() / 2. - 400. / 2.,
+                    screen_height() - 155.,
+                    400.,
+                    50.,
+                ),
+                tx.clone(),
+                false,
+            ),
+            history: MenuButton::new(
+                "history".to_string(),
+                vec!["History".to_string()],
+                Popout::None,
+                Rect::new(screen_width() / 2. - 400. / 2., 5., 400., 50.),
+                tx.clone(),
+                false,
+            ),
+            rescan: MenuButton::new(
+                "rescan".to_string(),
+                vec!["Rescan library".to_string()],
+                Popout::None,
+                Rect::new(screen_width() / 2. - 400. / 2., 60., 400., 50.),
+                tx.clone(),
+                false,
+            ),
+            import: MenuButton::new(
+                "import".to_string(),
+                vec!["Import .osz".to_string()],
+                Popout::None,
+                Rect::new(screen_width() / 2. - 400. / 2., 115., 400., 50.),
+                tx.clone(),
+                false,
+            ),
+            #[cfg(target_family = "wasm")]
+            import_folder: MenuButton::new(
+                "import_folder".to_string(),
+                vec!["Import folder".to_string()],
+                Popout::None,
+                Rect::new(screen_width() / 2. - 400. / 2., 170., 400., 50.),
+                tx.clone(),
+                false,
+            ),
+            #[cfg(target_family = "wasm")]
+            picked: None,
             loading_promise: None,
             local_lb: None,
             global_lb: None,
             scroll_target: None,
-            chart_data: None,
+            difficulty: None,
+            difficulty_key: None,
+            difficulty_due: None,
+            calculated: None,
+            practice_range: None,
+            practice_drag: None,
             started_map: Cell::new(false),
             pause: MenuButton::new(
                 "pause".to_string(),
@@ -293,7 +274,17 @@ impl SelectScreen {
         }
     }
 
+    /// Whether a difficulty is selected. Nothing is when the library is empty.
+    fn has_selection(&self) -> bool {
+        self.charts.get(self.selected_chart).map_or(false, |chart| {
+            self.selected_difficulty < chart.difficulties.len()
+        })
+    }
+
     fn start_map(&self, data: SharedGameData) {
+        if !self.has_selection() {
+            return;
+        }
         self.started_map.set(true);
         let chart = &self.charts[self.selected_chart];
         data.broadcast(GameMessage::load_screen({
@@ -303,6 +294,89 @@ impl SelectScreen {
             async move { Gameplay::new(data, &chart_title, &diff_name).await }
         }));
     }
+
+    /// Rescans the library, then shows it again so added and removed charts show up.
+    async fn reload_library(data: SharedGameData) {
+        let rescanned = data.library.borrow_mut().rescan().await;
+        if let Err(e) = rescanned {
+            log!(LogType::General, "Couldn't scan the chart library: {}", e);
+        }
+        data.broadcast(GameMessage::change_screen(
+            SelectScreen::new(data.clone()).await,
+        ));
+    }
+
+    /// Imports the beatmap sets in `files` into browser storage.
+    #[cfg(target_family = "wasm")]
+    async fn import_picked(files: Vec<import::File>) {
+        for set in import::picked_sets(files) {
+            let imported = match set.and_then(|files| import::convert_set(&files)) {
+                Ok(mut folder) => import::install(&mut folder).await.map(|()| folder),
+                Err(e) => Err(e),
+            };
+            match imported {
+                Ok(folder) => import::log_imported(&folder),
+                Err(e) => log!(LogType::General, "Couldn't import: {}", e),
+            }
+        }
+    }
+
+    /// Shows the cached difficulty for `difficulty_key`, or calculates it in the background.
+    fn request_difficulty(&mut self, data: SharedGameData) {
+        let (diff_id, mods) = match self.difficulty_key.clone() {
+            Some(key) => key,
+            None => return,
+        };
+        // Difficulties are hashed when the library is scanned.
+        let hash = data.state().difficulty().hash.clone();
+        if hash.is_empty() {
+            return;
+        }
+        if let Some(difficulty) = data.difficulty_cache.get(&hash, &mods) {
+            self.difficulty = Some(difficulty);
+            self.calculated = None;
+            return;
+        }
+
+        let (tx, rx) = flume::bounded(1);
+        self.calculated = Some(rx);
+        let chart_title = data.state().chart.title.clone();
+        let diff_name = data.state().difficulty().name.clone();
+        let data_clone = data.clone();
+        data.promises().spawn_detached(async move {
+            let beatmap_data = load_beatmap(&chart_title, &diff_name).await;
+            let attributes =
+                difficulty::calculate_async(move || convert_beatmap(&beatmap_data), mods.clone())
+                    .await;
+            let attributes = data_clone.difficulty_cache.insert(&hash, &mods, attributes);
+            // Nobody is waiting anymore if another difficulty was requested in the meantime.
+            let _ = tx.send(((diff_id, mods), attributes));
+        });
+    }
+
+    /// Section picked on the strain timeline, in chart time.
+    fn practice_section(&self) -> Option<Section> {
+        let (start, end) = self.practice_range?;
+        let length = self.difficulty.as_ref()?.length;
+        Some(Section {
+            start: start * length,
+            end: end * length,
+        })
+    }
+
+    fn start_practice(&self, data: SharedGameData, section: Section) {
+        if !self.has_selection() {
+            return;
+        }
+        self.started_map.set(true);
+        let chart = &self.charts[self.selected_chart];
+        data.broadcast(GameMessage::load_screen({
+            let data = data.clone();
+            let chart_title = chart.title.clone();
+            let diff_name = chart.difficulties[self.selected_difficulty].name.clone();
+            async move { Gameplay::practice(data, &chart_title, &diff_name, section).await }
+        }));
+    }
 }
 
 #[async_trait(?Send)]
@@ -317,41 +391,73 @@ impl Screen for SelectScreen {
             self.loading_promise = Some(data.promises().spawn(async move {
                 let title = data_clone.state().chart.title.clone();
 
-                let files = load_file(&format!("resources/{}/files.json", title))
-                    .await
-                    .unwrap();
-                let files: Vec<String> = serde_json::from_slice(&files).unwrap();
+                let (files, audio_file) = match library::read_manifest(&title).await {
+                    Ok(manifest) => manifest,
+                    Err(e) => {
+                        log!(LogType::General, "Couldn't load {}: {}", title, e);
+                        return None;
+                    }
+                };
+                // Imported charts only have a background if their beatmap set did.
+                let has_background = files.iter().any(|path| path == "bg.png");
                 files.into_iter().for_each(|path| {
                     data_clone
                         .audio_cache
-                        .whitelist(format!("resources/{}/{}", title, path))
+                        .whitelist(library::chart_file(&title, &path))
                 });
 
-                let sound = data_clone
+                let sound = match data_clone
                     .audio_cache
                     .get_sound(
-                        &format!("resources/{}/audio.wav", title),
+                        &library::chart_file(&title, &audio_file),
                         data_clone.main_track.id(),
                     )
                     .await
-                    .unwrap();
-                let background = data_clone
-                    .image_cache
-                    .get_texture(&format!(
-                        "resources/{}/bg.png",
-                        data_clone.state().chart.title
-                    ))
-                    .await;
-                (sound, background)
+                {
+                    Ok(sound) => sound,
+                    Err(e) => {
+                        log!(
+                            LogType::General,
+                            "Couldn't load the music of {}: {:?}",
+                            title,
+                            e
+                        );
+                        return None;
+                    }
+                };
+                let background = if has_background {
+                    Some(
+                        data_clone
+                            .image_cache
+                            .get_texture(&library::chart_file(&title, "bg.png"))
+                            .await,
+                    )
+                } else {
+                    None
+                };
+                Some((sound, background))
             }));
 
             self.prev_selected_chart = self.selected_chart;
         }
 
+        #[cfg(target_family = "wasm")]
+        if let Some(files) = self
+            .picked
+            .as_ref()
+            .and_then(|picked| picked.try_recv().ok())
+        {
+            self.picked = None;
+            SelectScreen::import_picked(files).await;
+            SelectScreen::reload_library(data.clone()).await;
+        }
+
         if let Some(loading_promise) = &self.loading_promise {
-            if let Some((sound, background)) = data.promises().try_get(loading_promise) {
-                data.background.set(Some(background));
-                data.broadcast(GameMessage::update_music_looped(sound));
+            if let Some(loaded) = data.promises().try_get(loading_promise) {
+                if let Some((sound, background)) = loaded {
+                    data.background.set(background);
+                    data.broadcast(GameMessage::update_music_looped(sound));
+                }
 
                 self.loading_promise = None;
             }
@@ -392,7 +498,9 @@ impl Screen for SelectScreen {
             self.scroll_vel -= self.scroll_vel * get_frame_time() * 5.;
         }
 
-        if data.is_key_pressed(KeyCode::Right) {
+        // There's nothing to move between in an empty library.
+        let has_charts = !self.chart_list.buttons.is_empty();
+        if has_charts && data.is_key_pressed(KeyCode::Right) {
             self.tx
                 .send(Message {
                     target: self.chart_list.id.clone(),
@@ -401,7 +509,7 @@ impl Screen for SelectScreen {
                     )),
                 })
                 .unwrap();
-        } else if data.is_key_pressed(KeyCode::Left) {
+        } else if has_charts && data.is_key_pressed(KeyCode::Left) {
             self.tx
                 .send(Message {
                     target: self.chart_list.id.clone(),
@@ -413,7 +521,7 @@ impl Screen for SelectScreen {
                 .unwrap();
         }
 
-        if data.is_key_pressed(KeyCode::Down) {
+        if has_charts && data.is_key_pressed(KeyCode::Down) {
             self.tx
                 .send(Message {
                     target: self.chart_list.id.clone(),
@@ -424,7 +532,7 @@ impl Screen for SelectScreen {
                 })
                 .unwrap();
         }
-        if data.is_key_pressed(KeyCode::Up) {
+        if has_charts && data.is_key_pressed(KeyCode::Up) {
             let len = self.chart_list.buttons[self.chart_list.selected].1.len();
             self.tx
                 .send(Message {
@@ -444,6 +552,12 @@ impl Screen for SelectScreen {
             if !self.started_map.get() {
                 self.chart_list.handle_message(&message);
                 self.start.handle_message(&message);
+                self.practice.handle_message(&message);
+                self.history.handle_message(&message);
+                self.rescan.handle_message(&message);
+                self.import.handle_message(&message);
+                #[cfg(target_family = "wasm")]
+                self.import_folder.handle_message(&message);
                 self.pause.handle_message(&message);
                 if let Some(leaderboard) = &mut self.local_lb {
                     leaderboard.handle_message(&message);
@@ -464,18 +578,49 @@ impl Screen for SelectScreen {
                         message.data
                     {
                         self.selected_difficulty = idx;
+                        self.practice_range = None;
                         data.state.borrow_mut().difficulty_idx = idx;
-                        let diff_id = data.state().chart.difficulties[idx].id;
-
-                        let entries = data.state_mut().leaderboard.query_local(diff_id).await;
+                        let diff_hash = data.state().chart.difficulties[idx].hash.clone();
+
+                        let entries = data
+                            .state_mut()
+                            .leaderboard
+                            .query_local(&diff_hash)
+                            .await
+                            .unwrap_or_else(|e| {
+                                log!(LogType::General, "Couldn't load local scores: {}", e);
+                                Vec::new()
+                            });
+                        let model = data.scoring_model.get();
+                        let mut entries = entries
+                            .into_iter()
+                            .map(|entry| {
+                                let score = ranked_score(
+                                    model,
+                                    entry.model,
+                                    entry.score,
+                                    &entry.judgements,
+                                    entry.top_combo,
+                                    &entry.mods,
+                                );
+                                (score, entry)
+                            })
+                            .collect::<Vec<_>>();
+                        entries.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
                         let button_title = entries
                             .iter()
-                            .map(|entry| {
-                                vec![format!(
-                                    "{} ({:.2}%)",
-                                    entry.score.to_formatted_string(&Locale::en),
-                                    entry.accuracy * 100.
-                                )]
+                            .map(|(score, entry)| {
+                                vec![
+                                    format!(
+                                        "{} {}{} ({:.2}%) {:.0}pp",
+                                        entry.grade.name(),
+                                        if entry.model == model { "" } else { "~" },
+                                        score.to_formatted_string(&Locale::en),
+                                        entry.accuracy * 100.,
+                                        entry.pp
+                                    ),
+                                    entry_details(&entry.judgements, &entry.mods),
+                                ]
                             })
                             .collect::<Vec<_>>();
                         self.local_lb = Some(MenuButtonList::new(
@@ -494,25 +639,7 @@ impl Screen for SelectScreen {
                         );
 
                         self.global_lb = None;
-                        data.send_server(ClientPacket::RequestLeaderboard(diff_id));
-
-                        let chart_info = &self.charts[self.selected_chart];
-                        let beatmap_data = load_file(&format!(
-                            "resources/{}/{}.osu",
-                            chart_info.title,
-                            chart_info.difficulties[self.selected_difficulty].name
-                        ))
-                        .await
-                        .unwrap();
-                        let beatmap_content = std::str::from_utf8(&beatmap_data).unwrap();
-                        let beatmap = osu_parser::load_content(
-                            beatmap_content,
-                            osu_parser::BeatmapParseOptions::default(),
-                        )
-                        .unwrap();
-                        self.chart_data = Some(ChartCalcData::new(
-                            &crate::chart::Chart::convert_from(&beatmap),
-                        ));
+                        data.send_server(ClientPacket::RequestLeaderboard(diff_hash));
                     }
                 }
                 if message.target == self.start.id {
@@ -520,6 +647,60 @@ impl Screen for SelectScreen {
                         self.start_map(data.clone());
                     }
                 }
+                if message.target == self.history.id {
+                    if let MessageData::MenuButton(MenuButtonMessage::Selected) = message.data {
+                        data.broadcast(GameMessage::change_screen(
+                            HistoryScreen::new(data.clone()).await,
+                        ));
+                    }
+                }
+                if message.target == self.rescan.id {
+                    if let MessageData::MenuButton(MenuButtonMessage::Selected) = message.data {
+                        SelectScreen::reload_library(data.clone()).await;
+                    }
+                }
+                #[cfg(not(target_family = "wasm"))]
+                if message.target == self.import.id {
+                    if let MessageData::MenuButton(MenuButtonMessage::Selected) = message.data {
+                        let paths = rfd::FileDialog::new()
+                            .add_filter("osu! beatmap set", &["osz"])
+                            .pick_files()
+                            .unwrap_or_default();
+                        for path in &paths {
+                            match import::import_osz(path) {
+                                Ok(folder) => import::log_imported(&folder),
+                                Err(e) => log!(
+                                    LogType::General,
+                                    "Couldn't import {}: {}",
+                                    path.display(),
+                                    e
+                                ),
+                            }
+                        }
+                        if !paths.is_empty() {
+                            SelectScreen::reload_library(data.clone()).await;
+                        }
+                    }
+                }
+                #[cfg(target_family = "wasm")]
+                if message.target == self.import.id {
+                    if let MessageData::MenuButton(MenuButtonMessage::Selected) = message.data {
+                        self.picked = Some(import::pick_files(false));
+                    }
+                }
+                #[cfg(target_family = "wasm")]
+                if message.target == self.import_folder.id {
+                    if let MessageData::MenuButton(MenuButtonMessage::Selected) = message.data {
+                        self.picked = Some(import::pick_files(true));
+                    }
+                }
+                if message.target == self.practice.id {
+                    if let MessageData::MenuButton(MenuButtonMessage::Selected) = message.data {
+                        if let Some(section) = self.practice_section() {
+                            self.start_practice(data.clone(), section);
+                        }
+                    }
+                }
 
                 if message.target == self.pause.id {
                     if let MessageData::MenuButton(MenuButtonMessage::Selected) = message.data {
@@ -534,8 +715,65 @@ impl Screen for SelectScreen {
                 }
             }
         }
+        // Mods can change while on this screen, e.g. while dragging the rate slider.
+        let key = (data.state().difficulty().id, data.mods.borrow().clone());
+        if self.difficulty_key.as_ref() != Some(&key) {
+            let same_difficulty = self.difficulty_key.as_ref().map(|(id, _)| *id) == Some(key.0);
+            if !same_difficulty {
+                self.difficulty = None;
+            }
+            self.difficulty_due = Some(if same_difficulty {
+                get_time() + DIFFICULTY_DEBOUNCE
+            } else {
+                get_time()
+            });
+            self.difficulty_key = Some(key);
+        }
+        if self.difficulty_due.map_or(false, |due| get_time() >= due) {
+            self.difficulty_due = None;
+            self.request_difficulty(data.clone());
+        }
+        if let Some((key, difficulty)) = self
+            .calculated
+            .as_ref()
+            .and_then(|calculated| calculated.try_recv().ok())
+        {
+            self.calculated = None;
+            if self.difficulty_key.as_ref() == Some(&key) {
+                self.difficulty = Some(difficulty);
+            }
+        }
+
+        // Dragging across the strain timeline picks a section to practice, clicking clears it.
+        let timeline = strain_timeline_bounds();
+        let (mouse_x, mouse_y) = mouse_position();
+        let fraction = ((mouse_x - timeline.x) / timeline.w).clamp(0., 1.);
+        if is_mouse_button_pressed(MouseButton::Left)
+            && self.difficulty.is_some()
+            && timeline.contains(vec2(mouse_x, mouse_y))
+        {
+            self.practice_drag = Some(fraction);
+        }
+        if let Some(drag_start) = self.practice_drag {
+            self.practice_range = Some((drag_start.min(fraction), drag_start.max(fraction)));
+            if !is_mouse_button_down(MouseButton::Left) {
+                self.practice_drag = None;
+                if (fraction - drag_start).abs() < 0.01 {
+                    self.practice_range = None;
+                }
+            }
+        }
+
         self.chart_list.update(data.clone());
         self.start.update(data.clone());
+        self.history.update(data.clone());
+        self.rescan.update(data.clone());
+        self.import.update(data.clone());
+        #[cfg(target_family = "wasm")]
+        self.import_folder.update(data.clone());
+        if self.practice_range.is_some() {
+            self.practice.update(data.clone());
+        }
         self.pause.update(data.clone());
         if let Some(local) = &mut self.local_lb {
             local.update(data.clone());
@@ -558,6 +796,14 @@ impl Screen for SelectScreen {
         );
         self.chart_list.draw(data.clone());
         self.start.draw(data.clone());
+        self.history.draw(data.clone());
+        self.rescan.draw(data.clone());
+        self.import.draw(data.clone());
+        #[cfg(target_family = "wasm")]
+        self.import_folder.draw(data.clone());
+        if self.practice_range.is_some() {
+            self.practice.draw(data.clone());
+        }
         self.pause.draw(data.clone());
         if let Some(local) = &self.local_lb {
             local.draw(data.clone());
@@ -566,56 +812,63 @@ impl Screen for SelectScreen {
             global.draw(data);
         }
 
-        if let Some(chart_data) = &self.chart_data {
-            let max_density = chart_data.density.iter().last().unwrap();
-            let max_angle = chart_data.angles.iter().last().unwrap();
-            let max_angle_change = chart_data.angles.iter().last().unwrap();
-
-            let spacing = 100.;
+        if let Some(difficulty) = &self.difficulty {
             draw_text(
-                &format!("Density ({:.2})", chart_data.avg_density()),
-                0.,
-                screen_height() - 106.,
-                16.,
+                &format!("{:.2} stars", difficulty.star_rating),
+                5.,
+                screen_height() - 126.,
+                24.,
                 WHITE,
             );
-            draw_visualization(
-                0.,
-                screen_height() - 100.,
-                50.,
-                100.,
-                interpolate(max_density.0.raw(), &chart_data.density),
-            );
-
             draw_text(
-                &format!("Angles ({:.2})", chart_data.avg_angles()),
-                spacing + 5.,
+                &format!(
+                    "Movement {:.1} / Direction changes {:.1} / Hyperdash {:.1} / Density {:.1}",
+                    difficulty.movement,
+                    difficulty.direction_changes,
+                    difficulty.hyperdash,
+                    difficulty.density
+                ),
+                5.,
                 screen_height() - 106.,
                 16.,
                 WHITE,
             );
-            draw_visualization(
-                spacing + 5.,
-                screen_height() - 100.,
-                50.,
-                100.,
-                interpolate(max_angle.0.raw(), &chart_data.angles),
+            let timeline = strain_timeline_bounds();
+            draw_strain_timeline(
+                timeline.x,
+                timeline.y,
+                timeline.w,
+                timeline.h,
+                &difficulty.strains,
             );
+            if let (Some((start, end)), Some(section)) =
+                (self.practice_range, self.practice_section())
+            {
+                draw_rectangle(
+                    timeline.x + start * timeline.w,
+                    timeline.y,
+                    (end - start) * timeline.w,
+                    timeline.h,
+                    Color::new(0.3, 0.6, 1., 0.35),
+                );
+                draw_text(
+                    &format!("{:.1}s - {:.1}s", section.start, section.end),
+                    timeline.x + start * timeline.w,
+                    timeline.y + 16.,
+                    16.,
+                    WHITE,
+                );
+            }
+        }
 
-            draw_text(
-                &format!("Angle Changes ({:.2})", chart_data.avg_angle_changes()),
-                spacing * 2. + 10.,
-                screen_height() - 106.,
-                16.,
+        if self.charts.is_empty() {
+            draw_text_centered(
+                "No charts yet, import a map to start playing",
+                screen_width() / 2.,
+                screen_height() / 2.,
+                36,
                 WHITE,
             );
-            draw_visualization(
-                spacing * 2. + 10.,
-                screen_height() - 100.,
-                50.,
-                100.,
-                interpolate(max_angle_change.0.raw(), &chart_data.angle_changes),
-            );
         }
 
         if self.loading_promise.is_some() {
@@ -656,19 +909,38 @@ impl Screen for SelectScreen {
     fn handle_packet(&mut self, data: SharedGameData, packet: &ServerPacket) {
         #[allow(clippy::single_match)]
         match packet {
-            ServerPacket::Leaderboard { diff_id, scores } => {
-                let current_diff_id = data.state().difficulty().id;
-                if *diff_id == current_diff_id {
-                    let button_title = scores
+            ServerPacket::Leaderboard { diff_hash, scores } => {
+                if *diff_hash == data.state().difficulty().hash {
+                    let model = data.scoring_model.get();
+                    let mut scores = scores
                         .iter()
                         .map(|score| {
+                            let ranked = ranked_score(
+                                model,
+                                score.model,
+                                score.score,
+                                &score.judgements,
+                                score.top_combo,
+                                &score.mods,
+                            );
+                            (ranked, score)
+                        })
+                        .collect::<Vec<_>>();
+                    scores.sort_by_key(|(ranked, _)| std::cmp::Reverse(*ranked));
+                    let button_title = scores
+                        .iter()
+                        .map(|(ranked, score)| {
                             vec![
                                 score.username.clone().unwrap(),
                                 format!(
-                                    "{} ({:.2}%)",
-                                    score.score.to_formatted_string(&Locale::en),
-                                    score::accuracy(&score.judgements) * 100.
+                                    "{} {}{} ({:.2}%) {:.0}pp",
+                                    score.grade.name(),
+                                    if score.model == model { "" } else { "~" },
+                                    ranked.to_formatted_string(&Locale::en),
+                                    score::accuracy(&score.judgements) * 100.,
+                                    score.pp
                                 ),
+                                entry_details(&score.judgements, &score.mods),
                             ]
                         })
                         .collect::<Vec<_>>();
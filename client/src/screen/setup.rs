@@ -11,6 +11,7 @@ use macroquad::prelude::*;
 
 use super::{
     game::{GameMessage, SharedGameData},
+    keybinds::KeyBindScreen,
     select::SelectScreen,
     Screen,
 };
@@ -36,7 +37,7 @@ impl SetupScreen {
                 vec![
                     vec!["Left-handed (A D RShift)".into()],
                     vec!["Right-handed (Left Right LShift)".into()],
-                    vec!["Custom (TODO)".into()],
+                    vec!["Custom".into()],
                 ],
                 tx,
             ),
@@ -60,20 +61,17 @@ impl Screen for SetupScreen {
                     message.data
                 {
                     let key_binds = match idx {
-                        0 => KeyBinds {
-                            right: KeyCode::D,
-                            left: KeyCode::A,
-                            dash: KeyCode::RightShift,
-                        },
-                        1 => KeyBinds {
-                            right: KeyCode::Right,
-                            left: KeyCode::Left,
-                            dash: KeyCode::LeftShift,
-                        },
-                        _ => todo!(),
+                        0 => KeyBinds::left_handed(),
+                        1 => KeyBinds::right_handed(),
+                        _ => {
+                            data.broadcast(GameMessage::change_screen(KeyBindScreen::new(
+                                data.clone(),
+                            )));
+                            continue;
+                        }
                     };
                     set_value("first_time", false);
-                    set_value("binds", key_binds);
+                    set_value("binds", &key_binds);
                     data.state.borrow_mut().binds = key_binds;
                     data.broadcast(GameMessage::change_screen(
                         SelectScreen::new(data.clone()).await,
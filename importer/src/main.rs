@@ -1,78 +1,146 @@
-use std::{ffi::OsStr, path::PathBuf, process::Command};
+use beatmap_import::{self as import, ChartFolder, File, INDEX, MANIFEST};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
-fn main() {
-    let song_path = PathBuf::from(std::env::args().nth(1).expect("Song path"));
-    let files = std::fs::read_dir(&song_path)
-        .unwrap()
-        .map(|res| res.map(|e| e.path()))
-        .collect::<Result<Vec<_>, std::io::Error>>()
-        .unwrap();
+/// Library folder charts are imported into.
+const LIBRARY: &str = "resources";
 
-    let diffs = files
-        .iter()
-        .filter(|f| f.extension() == Some(OsStr::new("osu")))
+fn main() {
+    let paths = std::env::args()
+        .skip(1)
+        .map(PathBuf::from)
         .collect::<Vec<_>>();
+    if paths.is_empty() {
+        eprintln!("Usage: importer <.osz file, beatmap set folder or Songs folder>...");
+        std::process::exit(1);
+    }
 
-    let base_diff_path = *diffs.first().unwrap();
-    let base_diff = osu_parser::load_content(
-        &std::fs::read_to_string(base_diff_path).unwrap(),
-        osu_parser::BeatmapParseOptions::default(),
-    )
-    .unwrap();
+    let library = PathBuf::from(LIBRARY);
+    std::fs::create_dir_all(&library).unwrap();
+    let (mut imported, mut failed) = (0, 0);
+    for set in paths.iter().flat_map(|path| beatmap_sets(path)) {
+        let name = set.file_name().and_then(OsStr::to_str).unwrap_or("?");
+        match import_set(&set, &library) {
+            Ok(folder) => {
+                imported += 1;
+                println!("{}: {}", name, summary(&folder));
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{}: failed, {}", name, e);
+            }
+        }
+    }
+    println!("Imported {} sets, {} failed", imported, failed);
 
-    let audio_file_path = &base_diff.info.general_data.audio_file_name;
-    let bg_file_path = match &base_diff.events.first().unwrap() {
-        osu_types::Event::Background { filename, .. } => filename,
-        _ => panic!(),
-    };
-    let set_name = base_diff.info.metadata.title;
+    update_index(&library);
+}
 
-    let target_dir = PathBuf::from("resources").join(set_name);
-    std::fs::create_dir(&target_dir).unwrap();
+/// Beatmap sets at `path`: an `.osz`, a folder of beatmap files, or a Songs folder full of either.
+fn beatmap_sets(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() || has_beatmaps(path) {
+        return vec![path.to_owned()];
+    }
+    let mut sets = std::fs::read_dir(path)
+        .unwrap()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_dir() || path.extension() == Some(OsStr::new("osz")))
+        .collect::<Vec<_>>();
+    sets.sort();
+    sets
+}
 
-    Command::new("ffmpeg")
-        .args([
-            "-i",
-            song_path.join(audio_file_path).to_str().unwrap(),
-            target_dir.join("audio.wav").to_str().unwrap(),
-        ])
-        .output()
-        .unwrap();
+fn has_beatmaps(folder: &Path) -> bool {
+    std::fs::read_dir(folder).map_or(false, |mut entries| {
+        entries.any(|entry| {
+            entry.map_or(false, |entry| {
+                entry.path().extension() == Some(OsStr::new("osu"))
+            })
+        })
+    })
+}
 
-    Command::new("convert")
-        .args([
-            song_path.join(bg_file_path).to_str().unwrap(),
-            target_dir.join("bg.png").to_str().unwrap(),
-        ])
-        .output()
-        .unwrap();
+/// Converts the set at `path` and adds it to the library, next to what's already there.
+fn import_set(path: &Path, library: &Path) -> import::Result<ChartFolder> {
+    let files = if path.is_dir() {
+        let mut files = Vec::new();
+        read_folder(path, path, &mut files)?;
+        files
+    } else {
+        import::unpack_osz(&std::fs::read(path)?)?
+    };
+    let mut folder = import::convert_set(&files)?;
+    import::install(&mut folder, library)?;
+    Ok(folder)
+}
 
-    for diff in &diffs {
-        let beatmap = osu_parser::load_content(
-            &std::fs::read_to_string(diff).unwrap(),
-            osu_parser::BeatmapParseOptions::default(),
-        )
-        .unwrap();
-        std::fs::copy(
-            diff,
-            target_dir.join(format!("{}.osu", beatmap.info.metadata.version)),
-        )
-        .unwrap();
+/// Adds every file in `folder` to `files`, named by their path from `root`.
+fn read_folder(root: &Path, folder: &Path, files: &mut Vec<File>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            read_folder(root, &path, files)?;
+        } else {
+            let name = path
+                .strip_prefix(root)
+                .unwrap()
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push(File {
+                name,
+                content: std::fs::read(&path)?,
+            });
+        }
     }
+    Ok(())
+}
 
-    let file_listing = diffs
-        .into_iter()
-        .cloned()
-        .chain([PathBuf::from("audio.wav"), PathBuf::from("bg.png")].into_iter())
-        .map(|file| {
-            file.strip_prefix(&song_path)
-                .unwrap_or(file.as_path())
-                .to_str()
-                .unwrap()
-                .to_owned()
-        })
+/// What was imported as which chart, and what was skipped.
+fn summary(folder: &ChartFolder) -> String {
+    let difficulties = folder
+        .files
+        .iter()
+        .filter_map(|file| file.name.strip_suffix(".osu"))
         .collect::<Vec<_>>();
+    let mut summary = format!(
+        "imported {} difficulties into {} ({})",
+        difficulties.len(),
+        folder.title,
+        difficulties.join(", ")
+    );
+    if !folder.skipped.is_empty() {
+        summary.push_str(&format!(
+            ", skipped {} ({})",
+            folder.skipped.len(),
+            folder.skipped.join(", ")
+        ));
+    }
+    if let Some(e) = &folder.background_error {
+        summary.push_str(&format!(", left out the background ({})", e));
+    }
+    summary
+}
 
-    let files_json = serde_json::to_string_pretty(&file_listing).unwrap();
-    std::fs::write(target_dir.join("files.json"), files_json).unwrap();
+/// Lists every chart folder in the [`INDEX`], which the web client scans instead of the directory.
+fn update_index(library: &Path) {
+    let mut titles = std::fs::read_dir(library)
+        .unwrap()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if !path.join(MANIFEST).is_file() {
+                return None;
+            }
+            Some(path.file_name()?.to_str()?.to_owned())
+        })
+        .collect::<Vec<_>>();
+    titles.sort();
+    std::fs::write(
+        library.join(INDEX),
+        serde_json::to_string_pretty(&titles).unwrap(),
+    )
+    .unwrap();
 }
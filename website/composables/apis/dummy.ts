@@ -22,7 +22,8 @@ export const dummyApi: CtbWebApi = {
             return {
                 id,
                 username: "GamerDuck",
-                email: "GamerDuck123@email.com"
+                email: "GamerDuck123@email.com",
+                grades: { "s": 3, "a": 12, "b": 4 },
             }
         } else {
             return null;
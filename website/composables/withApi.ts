@@ -17,6 +17,8 @@ export interface UserData {
     id: number,
     username: string,
     email: string | null,
+    /** Number of scores set with each grade, keyed by grade ("ss-silver", "ss", ..., "d"). */
+    grades: Record<string, number>,
 }
 
 export interface CtbWebApi {